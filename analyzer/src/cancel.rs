@@ -0,0 +1,92 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use thiserror::Error;
+use tokio::sync::Notify;
+
+#[derive(Debug, Error)]
+#[error("The analysis was cancelled")]
+pub struct Cancelled;
+
+/// A flag shared between an analysis and the code that may abort it.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// Wakes the futures waiting in [`CancellationToken::cancelled`].
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Relaxed);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Wait until the token is cancelled.
+    pub async fn cancelled(&self) {
+        // Register before checking the flag, so that a concurrent cancel is not missed
+        let notified = self.inner.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Run `future` to completion, or drop it and fail with [`Cancelled`] once the
+    /// token is cancelled. Dropping a pending request cancels it on the server.
+    pub async fn run_until_cancelled<F: Future>(&self, future: F) -> Result<F::Output, Cancelled> {
+        tokio::select! {
+            output = future => Ok(output),
+            () = self.cancelled() => Err(Cancelled),
+        }
+    }
+
+    /// Fail with [`Cancelled`] if the token was cancelled.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn run_until_cancelled_drops_the_future_once_cancelled() {
+        let cancel = CancellationToken::new();
+        assert_eq!(cancel.run_until_cancelled(async { 1 }).await.unwrap(), 1);
+
+        let canceller = cancel.clone();
+        let pending = cancel.run_until_cancelled(future::pending::<()>());
+        let (result, ()) = tokio::join!(pending, async move { canceller.cancel() });
+        assert!(result.is_err());
+        assert!(cancel.check().is_err());
+    }
+
+    #[tokio::test]
+    async fn cancelled_returns_for_an_already_cancelled_token() {
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        cancel.cancelled().await;
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use graph::{EdgeData, Graph, NodeContents, NodeData, NodeId, Relation};
+
+/// Add a `Crate` node for every package of the Cargo workspace at `root_path`, as an
+/// additional parent of the files below the source directories of the package's targets.
+pub fn populate_crates(graph: &mut Graph, root_path: &Path) -> Result<(), anyhow::Error> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .current_dir(root_path)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let packages = metadata["packages"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("cargo metadata output lacks packages"))?;
+
+    let files: Vec<(NodeId, PathBuf)> = graph
+        .nodes()
+        .filter_map(|node_id| match &graph.node(node_id)?.contents {
+            NodeContents::File { path, .. } => Some((node_id, path.clone())),
+            _ => None,
+        })
+        .collect();
+
+    for package in packages {
+        let (Some(name), Some(manifest_path)) =
+            (package["name"].as_str(), package["manifest_path"].as_str())
+        else {
+            continue;
+        };
+        let manifest_path = PathBuf::from(manifest_path);
+        let package_dir = manifest_path.parent().unwrap_or(Path::new(""));
+        // Targets at the package root, such as build scripts, would otherwise claim
+        // every file of the package, so only their source file belongs to the crate
+        let mut source_dirs = Vec::new();
+        let mut source_files = Vec::new();
+        for target in package["targets"].as_array().into_iter().flatten() {
+            let is_build_script = target["kind"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|kind| kind == "custom-build");
+            let Some(src_path) = target["src_path"].as_str().map(Path::new) else {
+                continue;
+            };
+            match src_path.parent() {
+                Some(dir) if dir != package_dir && !is_build_script => {
+                    source_dirs.push(dir.to_owned())
+                }
+                _ => source_files.push(src_path.to_owned()),
+            }
+        }
+
+        // Several roots may belong to the same Cargo workspace
+        let existing = graph.find_first(|node| match &node.contents {
+            NodeContents::Crate {
+                manifest_path: path,
+                ..
+            } => *path == manifest_path,
+            _ => false,
+        });
+        let crate_node = match existing {
+            Some(crate_node) => crate_node,
+            None => graph.add_node(NodeData::new(NodeContents::Crate {
+                display_name: name.to_owned(),
+                manifest_path,
+            })),
+        };
+        for (file_id, path) in &files {
+            if source_dirs.iter().any(|dir| path.starts_with(dir)) || source_files.contains(path) {
+                graph.add_edge_deduped(EdgeData {
+                    from: crate_node,
+                    to: *file_id,
+                    relation: Relation::IsParentOf,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::file_structure::populate_file_structure;
+    use crate::{AnalyzerConfig, CancellationToken};
+
+    #[test]
+    fn crates_claim_their_source_files_once() {
+        let root = std::env::temp_dir().join(format!("analyzer-crates-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        // cargo metadata reports canonical paths
+        let root = root.canonicalize().unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        fs::write(root.join("build.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("src").join("lib.rs"), "").unwrap();
+
+        let mut graph = Graph::default();
+        populate_file_structure(
+            &mut graph,
+            &root,
+            None,
+            &AnalyzerConfig::default(),
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        let first = populate_crates(&mut graph, &root);
+        let second = populate_crates(&mut graph, &root);
+        fs::remove_dir_all(&root).unwrap();
+
+        first.unwrap();
+        second.unwrap();
+        let crates = graph.find(|node| matches!(node.contents, NodeContents::Crate { .. }));
+        assert_eq!(crates.len(), 1);
+        let mut claimed: Vec<_> = graph
+            .node_children(crates[0])
+            .unwrap()
+            .into_iter()
+            .map(|file| match &graph.node(file).unwrap().contents {
+                NodeContents::File { path, .. } => path.clone(),
+                _ => panic!("crates only claim files"),
+            })
+            .collect();
+        claimed.sort();
+        assert_eq!(
+            claimed,
+            [root.join("build.rs"), root.join("src").join("lib.rs")]
+        );
+    }
+}
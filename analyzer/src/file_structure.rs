@@ -1,89 +1,300 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
 
-use ignore::dir::Ignore;
-use ignore::dir::IgnoreBuilder;
+use ignore::{DirEntry, WalkBuilder, WalkState};
 
 use graph::{EdgeData, Graph, NodeContents, NodeData, NodeId, Relation};
 
-struct StackEntry {
-    parent_node: NodeId,
-    parent_path: PathBuf,
-    parent_ignore: Ignore,
+use crate::{AnalyzerConfig, CancellationToken};
+
+/// How the top of the file tree is built.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RootStrategy {
+    /// Each analyzed root is a top-level `Folder` node.
+    #[default]
+    SingleFolder,
+    /// A synthetic `Workspace` node is the parent of the folders of all roots.
+    SyntheticWorkspace,
+}
+
+/// The outcome of adding the contents of a directory to the graph.
+#[derive(Debug, Default)]
+pub(crate) struct WalkOutcome {
+    /// Whether the walk stopped early because `config.max_nodes` was reached.
+    pub truncated: bool,
+    /// Paths left out by ignore files, the hidden-file rule or `config.skip_dir_names`.
+    /// Only collected if `config.report_ignored` is set and the walk was not truncated.
+    pub ignored: Vec<PathBuf>,
 }
 
-pub fn populate_file_structure<P: AsRef<Path>>(
+/// Add the folders and files below `root_path` to the graph, below `parent_node` if given.
+pub(crate) fn populate_file_structure<P: AsRef<Path>>(
     graph: &mut Graph,
     root_path: P,
-) -> Result<(), anyhow::Error> {
+    parent_node: Option<NodeId>,
+    config: &AnalyzerConfig,
+    cancel: &CancellationToken,
+) -> Result<WalkOutcome, anyhow::Error> {
     let root_path = root_path.as_ref().to_owned();
     let root_node = create_root_node(graph, root_path.clone())?;
-    let root_ignore = IgnoreBuilder::new().hidden(true).build();
-    let (root_ignore, error) = root_ignore.add_parents(root_path.clone());
-    if let Some(error) = error {
-        return Err(error.into());
-    }
-    let (root_ignore, error) = root_ignore.add_child(&root_path);
-    if let Some(error) = error {
-        return Err(error.into());
-    }
-
-    let mut stack = vec![StackEntry {
-        parent_node: root_node,
-        parent_path: root_path,
-        parent_ignore: root_ignore,
-    }];
-    while let Some(entry) = stack.pop() {
-        let StackEntry {
-            parent_node,
-            parent_path,
-            parent_ignore,
-        } = entry;
-
-        let entries = fs::read_dir(parent_path)?;
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-            if parent_ignore.is_ignored(stack.len(), &entry).is_ignore() {
-                println!("Ignoring {:?}", path);
+    if let Some(parent_node) = parent_node {
+        graph.add_edge(EdgeData {
+            from: parent_node,
+            to: root_node,
+            relation: Relation::IsParentOf,
+        });
+    }
+    walk(graph, &root_path, root_node, config, cancel)
+}
+
+/// Add a synthetic `Workspace` node to be the parent of the analyzed roots,
+/// if `strategy` asks for one.
+pub(crate) fn create_workspace_node(graph: &mut Graph, strategy: RootStrategy) -> Option<NodeId> {
+    match strategy {
+        RootStrategy::SingleFolder => None,
+        RootStrategy::SyntheticWorkspace => {
+            Some(graph.add_node(NodeData::new(NodeContents::Workspace {
+                display_name: "workspace".to_owned(),
+            })))
+        }
+    }
+}
+
+/// Add a file or directory to a graph that already contains its parent folder,
+/// walking only the contents of the added directory.
+/// Returns `None` if the path is ignored.
+pub fn add_path(
+    graph: &mut Graph,
+    path: &Path,
+    config: &AnalyzerConfig,
+) -> Result<Option<NodeId>, anyhow::Error> {
+    if let Some(node) = graph.node_by_path(path) {
+        return Ok(Some(node));
+    }
+    let Some(parent_path) = path.parent() else {
+        anyhow::bail!("{} has no parent directory", path.display());
+    };
+    let Some(parent_node) = graph.node_by_path(parent_path) else {
+        anyhow::bail!("{} is not part of the graph", parent_path.display());
+    };
+    if !path.exists() {
+        anyhow::bail!("{} does not exist", path.display());
+    }
+
+    // Only entries that are not ignored are yielded by the walk
+    let entry = walk_builder(parent_path, config)
+        .max_depth(Some(1))
+        .build()
+        .filter_map(Result::ok)
+        .find(|entry| entry.depth() == 1 && entry.path() == path);
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
+
+    let node = add_entry_node(graph, parent_node, &entry);
+    if is_dir(&entry) {
+        walk(graph, path, node, config, &CancellationToken::new())?;
+    }
+
+    Ok(Some(node))
+}
+
+/// Remove a file or directory and everything below it from the graph.
+/// Returns whether the path was part of the graph.
+pub fn remove_path(graph: &mut Graph, path: &Path) -> bool {
+    let Some(node) = graph.node_by_path(path) else {
+        return false;
+    };
+    for descendant in graph.descendants(node) {
+        graph.remove_node(descendant);
+    }
+    graph.remove_node(node);
+    true
+}
+
+/// Add the contents of the directory at `root_path` below `root_node`.
+fn walk(
+    graph: &mut Graph,
+    root_path: &Path,
+    root_node: NodeId,
+    config: &AnalyzerConfig,
+    cancel: &CancellationToken,
+) -> Result<WalkOutcome, anyhow::Error> {
+    if config.parallel_walk {
+        let entries = collect_entries_parallel(root_path, config, cancel)?;
+        add_entries(
+            graph,
+            root_path,
+            root_node,
+            entries.into_iter().map(Ok),
+            config,
+            cancel,
+        )
+    } else {
+        let entries = walk_builder(root_path, config).build();
+        add_entries(graph, root_path, root_node, entries, config, cancel)
+    }
+}
+
+/// Discover the entries below `root_path` concurrently, sorted by path so that
+/// they are in the same order as those of the serial walk.
+fn collect_entries_parallel(
+    root_path: &Path,
+    config: &AnalyzerConfig,
+    cancel: &CancellationToken,
+) -> Result<Vec<DirEntry>, anyhow::Error> {
+    let entries = Mutex::new(Vec::new());
+    walk_builder(root_path, config).build_parallel().run(|| {
+        Box::new(|entry| {
+            if cancel.is_cancelled() {
+                return WalkState::Quit;
+            }
+            match entry {
+                Ok(entry) => entries.lock().unwrap().push(entry),
+                Err(error) => tracing::warn!(%error, "Skipping unreadable entry"),
+            }
+            WalkState::Continue
+        })
+    });
+    cancel.check()?;
+    let mut entries = entries.into_inner().unwrap();
+    entries.sort_unstable_by(|a, b| a.path().cmp(b.path()));
+    Ok(entries)
+}
+
+/// Add walked entries below `root_node`, where `root_path` is the walk root.
+fn add_entries(
+    graph: &mut Graph,
+    root_path: &Path,
+    root_node: NodeId,
+    entries: impl IntoIterator<Item = Result<DirEntry, ignore::Error>>,
+    config: &AnalyzerConfig,
+    cancel: &CancellationToken,
+) -> Result<WalkOutcome, anyhow::Error> {
+    // Directories always come before their contents
+    let mut folder_nodes = HashMap::from([(root_path.to_owned(), root_node)]);
+    let mut walked = HashSet::new();
+    for entry in entries {
+        cancel.check()?;
+        // Entries that cannot be read, such as symlink loops, are left out of the graph
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                tracing::warn!(%error, "Skipping unreadable entry");
                 continue;
             }
-            let display_name = entry.file_name().to_string_lossy().to_string();
-            let node = NodeData {
-                contents: if path.is_dir() {
-                    NodeContents::Folder {
-                        display_name,
-                        path: path.clone(),
-                    }
-                } else {
-                    NodeContents::File {
-                        display_name,
-                        path: path.clone(),
-                    }
-                },
-            };
-            let node = graph.add_node(node);
-            let edge = EdgeData {
-                from: parent_node,
-                to: node,
-                relation: Relation::IsParentOf,
-            };
-            graph.add_edge(edge);
-            if path.is_dir() {
-                let (ignore, error) = parent_ignore.add_child(&path);
-                if let Some(error) = error {
-                    return Err(error.into());
-                }
-                stack.push(StackEntry {
-                    parent_node: node,
-                    parent_path: path,
-                    parent_ignore: ignore,
-                });
+        };
+        if entry.depth() == 0 {
+            continue;
+        }
+        let path = entry.path();
+        let Some(&parent_node) = path.parent().and_then(|parent| folder_nodes.get(parent)) else {
+            continue;
+        };
+        if config
+            .max_nodes
+            .is_some_and(|max_nodes| graph.node_count() >= max_nodes)
+        {
+            return Ok(WalkOutcome {
+                truncated: true,
+                ignored: Vec::new(),
+            });
+        }
+        let node = add_entry_node(graph, parent_node, &entry);
+        if is_dir(&entry) {
+            folder_nodes.insert(path.to_owned(), node);
+        }
+        if config.report_ignored {
+            walked.insert(entry.into_path());
+        }
+    }
+
+    if !config.report_ignored {
+        return Ok(WalkOutcome::default());
+    }
+    // The walk does not report what it leaves out, so compare with the directory contents
+    let mut ignored = Vec::new();
+    for folder in folder_nodes.keys() {
+        for child in fs::read_dir(folder)? {
+            let child = child?.path();
+            if !walked.contains(&child) {
+                ignored.push(child);
             }
         }
     }
+    ignored.sort_unstable();
+
+    Ok(WalkOutcome {
+        truncated: false,
+        ignored,
+    })
+}
 
-    Ok(())
+/// The modification times of the files below `root_path` that the walk would add.
+pub(crate) fn modification_times(
+    root_path: &Path,
+    config: &AnalyzerConfig,
+) -> Result<HashMap<PathBuf, SystemTime>, anyhow::Error> {
+    let mut times = HashMap::new();
+    for entry in walk_builder(root_path, config).build() {
+        let entry = entry?;
+        if is_dir(&entry) {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        times.insert(entry.into_path(), modified);
+    }
+    Ok(times)
+}
+
+/// A walk below `path` honoring ignore files, skipping the directories in
+/// `config.skip_dir_names` and hidden files unless `config.include_hidden` is set.
+/// The serial walk visits entries by name. Symlinked directories are descended into,
+/// with their contents below the link.
+fn walk_builder(path: &Path, config: &AnalyzerConfig) -> WalkBuilder {
+    let skip_dir_names = config.skip_dir_names.clone();
+    let mut builder = WalkBuilder::new(path);
+    builder
+        .hidden(!config.include_hidden)
+        .follow_links(true)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            let skipped = entry.depth() > 0
+                && is_dir(entry)
+                && skip_dir_names
+                    .iter()
+                    .any(|name| entry.file_name() == name.as_str());
+            !skipped
+        });
+    builder
+}
+
+fn is_dir(entry: &DirEntry) -> bool {
+    entry
+        .file_type()
+        .is_some_and(|file_type| file_type.is_dir())
+}
+
+/// Add a node for a directory entry as a child of `parent_node`.
+fn add_entry_node(graph: &mut Graph, parent_node: NodeId, entry: &DirEntry) -> NodeId {
+    let path = entry.path().to_owned();
+    let display_name = entry.file_name().to_string_lossy().to_string();
+    let node = NodeData::new(if is_dir(entry) {
+        NodeContents::Folder { display_name, path }
+    } else {
+        NodeContents::File { display_name, path }
+    });
+    let node = graph.add_node(node);
+    let edge = EdgeData {
+        from: parent_node,
+        to: node,
+        relation: Relation::IsParentOf,
+    };
+    graph.add_edge(edge);
+    node
 }
 
 fn create_root_node(graph: &mut Graph, root_path: PathBuf) -> Result<NodeId, anyhow::Error> {
@@ -96,11 +307,424 @@ fn create_root_node(graph: &mut Graph, root_path: PathBuf) -> Result<NodeId, any
             root_path.display()
         );
     };
-    let root_node = NodeData {
-        contents: NodeContents::Folder {
-            display_name: dir_name.to_string_lossy().to_string(),
-            path: root_path,
-        },
-    };
+    let root_node = NodeData::new(NodeContents::Folder {
+        display_name: dir_name.to_string_lossy().to_string(),
+        path: root_path,
+    });
     Ok(graph.add_node(root_node))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::Cancelled;
+
+    /// The paths of the folder and file nodes below `root`, relative to it.
+    fn relative_paths(graph: &Graph, root: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = graph
+            .nodes()
+            .filter_map(|id| match &graph.node(id).unwrap().contents {
+                NodeContents::Folder { path, .. } | NodeContents::File { path, .. } => {
+                    Some(path.strip_prefix(root).unwrap().to_owned())
+                }
+                _ => None,
+            })
+            .collect();
+        paths.sort_unstable();
+        paths
+    }
+
+    /// The walk adds the same nodes as the hand-rolled walker it replaced did,
+    /// including the contents of symlinked directories.
+    #[cfg(unix)]
+    #[test]
+    fn walk_matches_previous_walker() {
+        let root = std::env::temp_dir().join(format!("analyzer-walk-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".ignore"), "ignored.rs\n").unwrap();
+        fs::write(root.join(".hidden.rs"), "").unwrap();
+        fs::write(root.join("ignored.rs"), "").unwrap();
+        fs::write(root.join("lib.rs"), "").unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("target/out.rs"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("linked")).unwrap();
+
+        let mut graph = Graph::default();
+        let config = AnalyzerConfig::default();
+        let outcome =
+            populate_file_structure(&mut graph, &root, None, &config, &CancellationToken::new());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(!outcome.unwrap().truncated);
+        let expected: Vec<PathBuf> = [
+            "",
+            "lib.rs",
+            "linked",
+            "linked/main.rs",
+            "src",
+            "src/main.rs",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(relative_paths(&graph, &root), expected);
+        assert_eq!(graph.node_count(), expected.len());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_skipped() {
+        let root = std::env::temp_dir().join(format!("analyzer-loop-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("src/root")).unwrap();
+        std::os::unix::fs::symlink("itself", root.join("itself")).unwrap();
+
+        let mut graph = Graph::default();
+        let config = AnalyzerConfig::default();
+        let outcome =
+            populate_file_structure(&mut graph, &root, None, &config, &CancellationToken::new());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(!outcome.unwrap().truncated);
+        let expected: Vec<PathBuf> = ["", "src", "src/lib.rs"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(relative_paths(&graph, &root), expected);
+    }
+
+    #[test]
+    fn ignored_paths_are_reported_when_asked() {
+        let root = std::env::temp_dir().join(format!("analyzer-ignored-{}", std::process::id()));
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".ignore"), "ignored.rs\n").unwrap();
+        fs::write(root.join("ignored.rs"), "").unwrap();
+        fs::write(root.join("lib.rs"), "").unwrap();
+
+        let config = AnalyzerConfig {
+            report_ignored: true,
+            ..AnalyzerConfig::default()
+        };
+        let reported = populate_file_structure(
+            &mut Graph::default(),
+            &root,
+            None,
+            &config,
+            &CancellationToken::new(),
+        );
+        let unreported = populate_file_structure(
+            &mut Graph::default(),
+            &root,
+            None,
+            &AnalyzerConfig::default(),
+            &CancellationToken::new(),
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            reported.unwrap().ignored,
+            [
+                root.join(".ignore"),
+                root.join("ignored.rs"),
+                root.join("target")
+            ]
+        );
+        assert!(unreported.unwrap().ignored.is_empty());
+    }
+
+    #[test]
+    fn hidden_files_are_walked_only_when_included() {
+        let root = std::env::temp_dir().join(format!("analyzer-hidden-{}", std::process::id()));
+        fs::create_dir_all(root.join(".github")).unwrap();
+        fs::write(root.join(".github/ci.yml"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+        fs::write(root.join("lib.rs"), "").unwrap();
+
+        let config = AnalyzerConfig {
+            include_hidden: true,
+            ..AnalyzerConfig::default()
+        };
+        let mut hiding = Graph::default();
+        let hiding_outcome = populate_file_structure(
+            &mut hiding,
+            &root,
+            None,
+            &AnalyzerConfig::default(),
+            &CancellationToken::new(),
+        );
+        let mut including = Graph::default();
+        let including_outcome = populate_file_structure(
+            &mut including,
+            &root,
+            None,
+            &config,
+            &CancellationToken::new(),
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(!hiding_outcome.unwrap().truncated);
+        assert!(!including_outcome.unwrap().truncated);
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(relative_paths(&hiding, &root), paths(&["", "lib.rs"]));
+        assert_eq!(
+            relative_paths(&including, &root),
+            paths(&["", ".github", ".github/ci.yml", ".hidden", "lib.rs"])
+        );
+    }
+
+    #[test]
+    fn walk_stops_at_max_nodes() {
+        let root = std::env::temp_dir().join(format!("analyzer-max-nodes-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+
+        let config = AnalyzerConfig {
+            max_nodes: Some(2),
+            ..AnalyzerConfig::default()
+        };
+        let mut complete = Graph::default();
+        let complete_outcome = populate_file_structure(
+            &mut complete,
+            &root,
+            None,
+            &AnalyzerConfig::default(),
+            &CancellationToken::new(),
+        );
+        let mut limited = Graph::default();
+        let limited_outcome = populate_file_structure(
+            &mut limited,
+            &root,
+            None,
+            &config,
+            &CancellationToken::new(),
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(!complete_outcome.unwrap().truncated);
+        assert_eq!(complete.node_count(), 4);
+        assert!(limited_outcome.unwrap().truncated);
+        assert_eq!(limited.node_count(), 2);
+    }
+
+    #[test]
+    fn each_root_gets_its_own_tree() {
+        let base = std::env::temp_dir().join(format!("analyzer-roots-{}", std::process::id()));
+        for root in ["first", "second"] {
+            fs::create_dir_all(base.join(root)).unwrap();
+            fs::write(base.join(root).join("lib.rs"), "").unwrap();
+        }
+
+        let config = AnalyzerConfig::default();
+        let mut graph = Graph::default();
+        let first = populate_file_structure(
+            &mut graph,
+            base.join("first"),
+            None,
+            &config,
+            &CancellationToken::new(),
+        );
+        let second = populate_file_structure(
+            &mut graph,
+            base.join("second"),
+            None,
+            &config,
+            &CancellationToken::new(),
+        );
+        fs::remove_dir_all(&base).unwrap();
+
+        assert!(!first.unwrap().truncated && !second.unwrap().truncated);
+        assert_eq!(graph.node_count(), 4);
+        let roots = graph.find(|node| matches!(node.contents, NodeContents::Folder { .. }));
+        assert_eq!(roots.len(), 2);
+        for root in roots {
+            assert_eq!(graph.depth(root), Some(0));
+            assert_eq!(graph.node_children(root).unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn root_strategies_build_the_top_of_the_tree() {
+        let base = std::env::temp_dir().join(format!("analyzer-strategy-{}", std::process::id()));
+        for root in ["first", "second"] {
+            fs::create_dir_all(base.join(root)).unwrap();
+        }
+
+        let build = |strategy| {
+            let mut graph = Graph::default();
+            let workspace = create_workspace_node(&mut graph, strategy);
+            for root in ["first", "second"] {
+                let config = AnalyzerConfig::default();
+                let cancel = CancellationToken::new();
+                populate_file_structure(&mut graph, base.join(root), workspace, &config, &cancel)
+                    .unwrap();
+            }
+            let top = graph.find(|node| {
+                matches!(
+                    node.contents,
+                    NodeContents::Workspace { .. } | NodeContents::Folder { .. }
+                )
+            });
+            let top: Vec<_> = top
+                .into_iter()
+                .filter(|&id| graph.depth(id) == Some(0))
+                .collect();
+            (graph, top)
+        };
+        let (single, single_top) = build(RootStrategy::SingleFolder);
+        let (workspace, workspace_top) = build(RootStrategy::SyntheticWorkspace);
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(single_top.len(), 2);
+        for root in single_top {
+            assert!(matches!(
+                single.node(root).unwrap().contents,
+                NodeContents::Folder { .. }
+            ));
+        }
+        let [top] = workspace_top[..] else {
+            panic!("Expected a single top-level node");
+        };
+        assert!(matches!(
+            workspace.node(top).unwrap().contents,
+            NodeContents::Workspace { .. }
+        ));
+        let children = workspace.node_children(top).unwrap();
+        assert_eq!(children.len(), 2);
+        for child in children {
+            assert!(matches!(
+                workspace.node(child).unwrap().contents,
+                NodeContents::Folder { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn added_and_removed_paths_update_the_tree() {
+        let root = std::env::temp_dir().join(format!("analyzer-add-path-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("main.rs"), "").unwrap();
+
+        let config = AnalyzerConfig::default();
+        let mut graph = Graph::default();
+        populate_file_structure(&mut graph, &root, None, &config, &CancellationToken::new())
+            .unwrap();
+        fs::create_dir_all(root.join("module")).unwrap();
+        fs::write(root.join("module").join("lib.rs"), "").unwrap();
+        fs::write(root.join(".ignore"), "generated.rs\n").unwrap();
+        fs::write(root.join("generated.rs"), "").unwrap();
+        let added = add_path(&mut graph, &root.join("module"), &config);
+        let ignored = add_path(&mut graph, &root.join("generated.rs"), &config);
+        let outside = add_path(&mut graph, &root.join("missing").join("lib.rs"), &config);
+        fs::remove_dir_all(&root).unwrap();
+
+        let module = added.unwrap().unwrap();
+        assert!(ignored.unwrap().is_none());
+        assert!(outside.is_err());
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(graph.node_children(module).unwrap().len(), 1);
+        assert_eq!(graph.depth(module), Some(1));
+
+        assert!(remove_path(&mut graph, &root.join("module")));
+        assert!(!remove_path(&mut graph, &root.join("module")));
+        assert_eq!(graph.node_count(), 2);
+    }
+
+    #[test]
+    fn skipped_directory_names_are_not_walked() {
+        let root = std::env::temp_dir().join(format!("analyzer-skip-dirs-{}", std::process::id()));
+        fs::create_dir_all(root.join("target").join("debug")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src").join("target"), "").unwrap();
+
+        let mut skipping = Graph::default();
+        let skipping_result = populate_file_structure(
+            &mut skipping,
+            &root,
+            None,
+            &AnalyzerConfig::default(),
+            &CancellationToken::new(),
+        );
+        let config = AnalyzerConfig {
+            skip_dir_names: Vec::new(),
+            ..AnalyzerConfig::default()
+        };
+        let mut complete = Graph::default();
+        let complete_result = populate_file_structure(
+            &mut complete,
+            &root,
+            None,
+            &config,
+            &CancellationToken::new(),
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        skipping_result.unwrap();
+        complete_result.unwrap();
+        // The root, `src` and the file named `target` inside it
+        assert_eq!(skipping.node_count(), 3);
+        assert_eq!(complete.node_count(), 5);
+    }
+
+    #[test]
+    fn cancelled_walks_fail() {
+        let root =
+            std::env::temp_dir().join(format!("analyzer-cancel-walk-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let mut graph = Graph::default();
+        let result =
+            populate_file_structure(&mut graph, &root, None, &AnalyzerConfig::default(), &cancel);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(result.unwrap_err().is::<Cancelled>());
+    }
+
+    #[test]
+    fn parallel_walk_matches_serial_walk() {
+        let root = std::env::temp_dir().join(format!("analyzer-parallel-{}", std::process::id()));
+        for dir in ["a/b/c", "a/d", "e"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "a/b/c/1.rs",
+            "a/b/2.rs",
+            "a/d/3.rs",
+            "a/4.rs",
+            "e/5.rs",
+            "6.rs",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let walk = |parallel_walk| {
+            let config = AnalyzerConfig {
+                parallel_walk,
+                ..AnalyzerConfig::default()
+            };
+            let mut graph = Graph::default();
+            populate_file_structure(&mut graph, &root, None, &config, &CancellationToken::new())
+                .unwrap();
+            graph
+        };
+        let serial = walk(false);
+        let parallel = walk(true);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(serial.node_count(), 12);
+        assert_eq!(parallel.node_count(), serial.node_count());
+        for id in serial.nodes() {
+            let expected = serde_json::to_value(serial.node(id).unwrap()).unwrap();
+            let actual = serde_json::to_value(parallel.node(id).unwrap()).unwrap();
+            assert_eq!(actual, expected);
+            assert_eq!(parallel.node_children(id), serial.node_children(id));
+        }
+    }
+}
@@ -1,36 +1,155 @@
-use std::{path::PathBuf, str::FromStr};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-use graph::Graph;
-use lsp::populate_symbols;
+use graph::{Graph, NodeId};
+use lsp::{path_to_uri, populate_diagnostics, populate_docs, populate_monikers, populate_symbols};
 use lsp_client::lsp_types::{
-    ClientCapabilities, InitializeParams, InitializedParams, Uri, WindowClientCapabilities,
+    ClientCapabilities, InitializeParams, InitializedParams, WindowClientCapabilities,
 };
 
+mod cancel;
+mod crates;
 mod file_structure;
 mod lsp;
+mod modules;
+mod server;
+mod snippet;
+mod watch;
 
-use file_structure::populate_file_structure;
+use file_structure::{create_workspace_node, populate_file_structure};
+
+pub use cancel::{CancellationToken, Cancelled};
+pub use file_structure::{add_path, remove_path, RootStrategy};
+pub use lsp::{language_id_for, symbol_position, SymbolFilter};
+pub use lsp_client::Shutdown;
+pub use server::ServerSpec;
+pub use watch::{FileChanges, Snapshot};
+
+/// Generated and vendored directories skipped by default.
+pub const DEFAULT_SKIP_DIR_NAMES: &[&str] = &["target", "node_modules", ".git", "vendor", "dist"];
+
+/// How long the server may take to initialize and index the workspace by default.
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long to wait for each step of the server shutdown before killing it.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct AnalyzerConfig {
+    /// The workspace roots to analyze. Defaults to the current directory if empty.
+    pub roots: Vec<PathBuf>,
+    /// The language server to start. Detected from the first root if `None`.
+    pub server: Option<ServerSpec>,
+    /// How long the server may take to initialize and index the workspace before it is killed.
+    pub startup_timeout: Duration,
+    /// Stop adding nodes once the graph contains this many.
+    pub max_nodes: Option<usize>,
+    /// Language IDs per file extension, overriding [`language_id_for`].
+    pub language_ids: HashMap<String, String>,
+    /// Language IDs of the files whose symbols are requested from the server.
+    pub symbol_languages: Vec<String>,
+    /// Leaves out document symbols, e.g. test modules, together with their children.
+    /// All symbols are added if `None`.
+    pub symbol_filter: Option<SymbolFilter>,
+    /// Group files by the Cargo crates they belong to, using `cargo metadata`.
+    pub crate_overlay: bool,
+    /// Replace invalid UTF-8 in source files instead of skipping those files.
+    pub lossy_utf8: bool,
+    /// Names of directories to skip in addition to the ignore rules, such as build output.
+    pub skip_dir_names: Vec<String>,
+    /// Include hidden files and directories, such as `.github`, in the walk.
+    pub include_hidden: bool,
+    /// How the top of the file tree is built.
+    pub root_strategy: RootStrategy,
+    /// Request the moniker of every item, identifying it across workspaces.
+    pub monikers: bool,
+    /// Store the hover text of every item as its documentation. Needs a request per item.
+    pub hover_docs: bool,
+    /// Keep the Markdown of hover texts instead of converting it to plain text.
+    pub raw_markdown_docs: bool,
+    /// Only request symbols for files modified after this time.
+    /// Older files are still added to the graph, but without their symbols.
+    pub since: Option<SystemTime>,
+    /// Read directories concurrently, which helps on slow file systems.
+    /// The resulting graph is the same as with the serial walk.
+    pub parallel_walk: bool,
+    /// Report the paths left out by the walk in [`Analysis::ignored`].
+    /// Needs to read every walked directory a second time.
+    pub report_ignored: bool,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            server: None,
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            max_nodes: None,
+            language_ids: HashMap::new(),
+            symbol_languages: vec!["rust".to_owned()],
+            symbol_filter: None,
+            crate_overlay: false,
+            lossy_utf8: false,
+            skip_dir_names: DEFAULT_SKIP_DIR_NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            include_hidden: false,
+            root_strategy: RootStrategy::default(),
+            monikers: false,
+            hover_docs: false,
+            raw_markdown_docs: false,
+            since: None,
+            parallel_walk: false,
+            report_ignored: false,
+        }
+    }
+}
+
+pub struct Analysis {
+    pub graph: Graph,
+    /// Whether the graph is incomplete because `max_nodes` was reached.
+    pub truncated: bool,
+    /// Paths left out of the graph by ignore rules, hidden-file rules or `skip_dir_names`.
+    /// Empty unless [`AnalyzerConfig::report_ignored`] is set.
+    pub ignored: Vec<PathBuf>,
+}
 
 pub struct Analyzer {
-    path: PathBuf,
+    roots: Vec<PathBuf>,
     lsp_client: lsp_client::LspClient,
+    config: AnalyzerConfig,
+    /// The documents opened on the server, which later analyses update instead of reopening.
+    open_documents: lsp::OpenDocuments,
 }
 
 impl Analyzer {
     pub async fn start() -> Result<Self, anyhow::Error> {
-        let lsp_client = lsp_client::LspClient::start("rust-analyzer")?;
-        let path = std::env::current_dir()?;
-        let path_uri = Uri::from_str(&format!("file://{}", path.to_str().unwrap()))?;
-        let name = path
-            .file_name()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get current directory name"))?
-            .to_string_lossy()
-            .to_string();
+        Self::start_with_config(AnalyzerConfig::default()).await
+    }
+
+    pub async fn start_with_config(config: AnalyzerConfig) -> Result<Self, anyhow::Error> {
+        let roots = if config.roots.is_empty() {
+            vec![std::env::current_dir()?]
+        } else {
+            config.roots.clone()
+        };
+        let server = config
+            .server
+            .clone()
+            .or_else(|| Self::detect_server(&roots[0]))
+            .unwrap_or_else(ServerSpec::rust_analyzer);
+        let lsp_client = lsp_client::LspClient::builder(&server.program)
+            .args(&server.args)
+            .start()?;
+        let workspace_folders = roots
+            .iter()
+            .map(|root| workspace_folder(root))
+            .collect::<Result<_, _>>()?;
         let params = InitializeParams {
-            workspace_folders: Some(vec![lsp_client::lsp_types::WorkspaceFolder {
-                uri: path_uri,
-                name,
-            }]),
+            workspace_folders: Some(workspace_folders),
             capabilities: ClientCapabilities {
                 window: Some(WindowClientCapabilities {
                     work_done_progress: Some(true),
@@ -49,28 +168,373 @@ impl Analyzer {
             },
             ..Default::default()
         };
-        lsp_client
-            .initialize(params, |_| InitializedParams {})
+        let startup = async {
+            lsp_client.collect_diagnostics().await?;
+            lsp_client
+                .initialize(params, |_| InitializedParams {})
+                .await?;
+            tracing::info!("Waiting for the server to index the workspace");
+            lsp_client.wait_for_indexing_to_complete().await?;
+            tracing::info!("Indexing finished");
+            Ok::<_, anyhow::Error>(())
+        };
+        let error = match tokio::time::timeout(config.startup_timeout, startup).await {
+            Ok(Ok(())) => None,
+            Ok(Err(error)) => Some(error),
+            Err(_) => Some(anyhow::anyhow!(
+                "{} did not initialize within {:?}",
+                server.program,
+                config.startup_timeout
+            )),
+        };
+        if let Some(error) = error {
+            let _ = lsp_client.kill().await;
+            return Err(error);
+        }
+
+        Ok(Self {
+            lsp_client,
+            roots,
+            config,
+            open_documents: Default::default(),
+        })
+    }
+
+    /// The language server for the project at `path`, based on marker files such as
+    /// `Cargo.toml`, `package.json` or `go.mod`.
+    pub fn detect_server(path: &Path) -> Option<ServerSpec> {
+        server::detect_server(path)
+    }
+
+    /// The modification times of the files below the analyzed roots, to detect changes.
+    pub fn snapshot(&self) -> Result<Snapshot, anyhow::Error> {
+        Snapshot::take(&self.roots, &self.config)
+    }
+
+    /// Tell the server about files changed on disk, before analyzing again.
+    pub async fn notify_changes(&self, changes: &FileChanges) -> Result<(), anyhow::Error> {
+        lsp::close_documents(&changes.deleted, &self.lsp_client, &self.open_documents).await?;
+        let params = lsp_client::lsp_types::DidChangeWatchedFilesParams {
+            changes: changes.file_events()?,
+        };
+        self.lsp_client.did_change_watched_files(params).await
+    }
+
+    /// Bring a graph from [`Analyzer::analyze`] up to date with files changed on disk,
+    /// without analyzing everything again. Deleted paths are removed, created ones are
+    /// added, and the symbols of created and changed files are requested again.
+    /// Returns whether the graph is incomplete because `max_nodes` was reached.
+    pub async fn update(
+        &self,
+        graph: &mut Graph,
+        changes: &FileChanges,
+    ) -> Result<bool, anyhow::Error> {
+        self.notify_changes(changes).await?;
+        for path in &changes.deleted {
+            // A directory deleted along with the file is removed as well
+            let mut removed = path.as_path();
+            while let Some(parent) = removed.parent() {
+                if parent.exists() || graph.node_by_path(parent).is_none() {
+                    break;
+                }
+                removed = parent;
+            }
+            remove_path(graph, removed);
+        }
+        for path in &changes.created {
+            // A file in a new directory is added along with the directory
+            let mut added = path.as_path();
+            while let Some(parent) = added.parent() {
+                if graph.node_by_path(parent).is_some() {
+                    break;
+                }
+                added = parent;
+            }
+            if let Err(error) = add_path(graph, added, &self.config) {
+                tracing::warn!(path = %path.display(), %error, "Skipping created file");
+            }
+        }
+
+        let mut refreshed = Vec::new();
+        let mut truncated = false;
+        for path in changes.created.iter().chain(&changes.changed) {
+            let Some(file_id) = graph.node_by_path(path) else {
+                continue;
+            };
+            refreshed.push(path_to_uri(path)?);
+            truncated |= lsp::refresh_document_symbols(
+                graph,
+                file_id,
+                &self.lsp_client,
+                &self.open_documents,
+                &self.config,
+            )
             .await?;
-        lsp_client.wait_for_indexing_to_complete().await?;
+            // The diagnostics of the file are added again below
+            graph.node_mut(file_id).unwrap().max_severity = None;
+            let items = graph.items_in_file(file_id);
+            if self.config.monikers {
+                lsp::populate_item_monikers(
+                    graph,
+                    items.clone(),
+                    &self.lsp_client,
+                    &CancellationToken::new(),
+                )
+                .await?;
+            }
+            if self.config.hover_docs {
+                lsp::populate_item_docs(
+                    graph,
+                    items,
+                    &self.lsp_client,
+                    &self.config,
+                    &CancellationToken::new(),
+                )
+                .await?;
+            }
+        }
+        modules::populate_module_files(graph);
+        let diagnostics: Vec<_> = self
+            .lsp_client
+            .diagnostics()
+            .into_iter()
+            .filter(|params| refreshed.contains(&params.uri))
+            .collect();
+        populate_diagnostics(graph, &diagnostics)?;
 
-        Ok(Self { lsp_client, path })
+        Ok(truncated)
     }
 
-    pub async fn stop(self) -> Result<(), anyhow::Error> {
-        self.lsp_client.shutdown().await?;
-        self.lsp_client.exit().await?;
-        Ok(())
+    pub async fn stop(self) -> Result<Shutdown, anyhow::Error> {
+        self.lsp_client.stop(SHUTDOWN_TIMEOUT).await
+    }
+
+    /// The source lines of an item node, or `None` if the node has no range or
+    /// its file cannot be read.
+    pub fn node_snippet(&self, graph: &Graph, id: NodeId) -> Option<String> {
+        snippet::node_snippet(graph, id, &mut snippet::FileCache::new())
+    }
+
+    /// The source lines of several item nodes, reading each file only once.
+    pub fn node_snippets(&self, graph: &Graph, ids: &[NodeId]) -> Vec<Option<String>> {
+        let mut cache = snippet::FileCache::new();
+        ids.iter()
+            .map(|&id| snippet::node_snippet(graph, id, &mut cache))
+            .collect()
     }
 
     pub async fn graph(&self) -> Result<Graph, anyhow::Error> {
+        Ok(self.analyze().await?.graph)
+    }
+
+    pub async fn analyze(&self) -> Result<Analysis, anyhow::Error> {
+        self.analyze_with_cancellation(&CancellationToken::new())
+            .await
+    }
+
+    /// Like [`Analyzer::analyze`], but fails with [`Cancelled`] once `cancel` is triggered.
+    pub async fn analyze_with_cancellation(
+        &self,
+        cancel: &CancellationToken,
+    ) -> Result<Analysis, anyhow::Error> {
         let mut graph = Graph::default();
 
-        populate_file_structure(&mut graph, &self.path)?;
-        populate_symbols(&mut graph, &self.lsp_client).await?;
+        let mut truncated = false;
+        let mut ignored = Vec::new();
+        let workspace_node = create_workspace_node(&mut graph, self.config.root_strategy);
+        for root in &self.roots {
+            tracing::info!(root = %root.display(), "Walking files");
+            let outcome =
+                populate_file_structure(&mut graph, root, workspace_node, &self.config, cancel)?;
+            tracing::info!(
+                root = %root.display(),
+                nodes = graph.node_count(),
+                ignored = outcome.ignored.len(),
+                truncated = outcome.truncated,
+                "Walk finished"
+            );
+            for path in &outcome.ignored {
+                tracing::debug!(path = %path.display(), "Ignored");
+            }
+            ignored.extend(outcome.ignored);
+            truncated = outcome.truncated;
+            if truncated {
+                break;
+            }
+        }
+        tracing::info!("Requesting symbols");
+        truncated = truncated
+            || populate_symbols(
+                &mut graph,
+                &self.lsp_client,
+                &self.open_documents,
+                &self.config,
+                cancel,
+            )
+            .await?;
+        tracing::info!(nodes = graph.node_count(), "Symbols added");
+        if self.config.monikers {
+            populate_monikers(&mut graph, &self.lsp_client, cancel).await?;
+        }
+        if self.config.hover_docs {
+            populate_docs(&mut graph, &self.lsp_client, &self.config, cancel).await?;
+        }
+        modules::populate_module_files(&mut graph);
+        populate_diagnostics(&mut graph, &self.lsp_client.diagnostics())?;
+        if self.config.crate_overlay {
+            for root in &self.roots {
+                crates::populate_crates(&mut graph, root)?;
+            }
+        }
+
+        Ok(Analysis {
+            graph,
+            truncated,
+            ignored,
+        })
+    }
+}
+
+/// Write the graph as pretty-printed JSON.
+pub fn write_json<W: Write>(graph: &Graph, mut writer: W) -> Result<(), anyhow::Error> {
+    serde_json::to_writer_pretty(&mut writer, graph)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the graph as JSON to the file at `output`, or to stdout if `output` is `-`.
+pub fn write_json_to(graph: &Graph, output: &Path) -> Result<(), anyhow::Error> {
+    if output == Path::new("-") {
+        write_json(graph, std::io::stdout().lock())
+    } else {
+        let file = std::fs::File::create(output)?;
+        write_json(graph, std::io::BufWriter::new(file))
+    }
+}
+
+fn workspace_folder(path: &Path) -> Result<lsp_client::lsp_types::WorkspaceFolder, anyhow::Error> {
+    let uri = path_to_uri(path)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Failed to get directory name of {}", path.display()))?
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Directory name of {} is not valid UTF-8", path.display()))?
+        .to_owned();
+    Ok(lsp_client::lsp_types::WorkspaceFolder { uri, name })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use tracing::instrument::WithSubscriber;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn workspace_folder_is_named_after_the_directory() {
+        let folder = workspace_folder(Path::new("/home/user/my project")).unwrap();
+        assert_eq!(folder.name, "my project");
+        assert_eq!(folder.uri.as_str(), "file:///home/user/my%20project");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn workspace_folder_needs_a_directory_name() {
+        assert!(workspace_folder(Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn written_json_reads_back_as_the_graph() {
+        let mut graph = Graph::default();
+        graph.add_node(graph::NodeData::new(graph::NodeContents::Folder {
+            display_name: "src".to_owned(),
+            path: PathBuf::from("src"),
+        }));
+
+        let mut buffer = Vec::new();
+        write_json(&graph, &mut buffer).unwrap();
+        assert!(buffer.ends_with(b"}\n"));
+
+        let path =
+            std::env::temp_dir().join(format!("analyzer-output-{}.json", std::process::id()));
+        write_json_to(&graph, &path).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(written, buffer);
+        let decoded: Graph = serde_json::from_slice(&written).unwrap();
+        assert_eq!(decoded.node_count(), 1);
+    }
+
+    /// A server that never answers `initialize` is killed once the startup timeout fires.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn startup_times_out_without_initialize_response() {
+        let config = AnalyzerConfig {
+            roots: vec![std::env::temp_dir()],
+            server: Some(ServerSpec::new("sleep", &["60"])),
+            startup_timeout: Duration::from_millis(200),
+            ..AnalyzerConfig::default()
+        };
+        let start = Instant::now();
+        let Err(error) = Analyzer::start_with_config(config).await else {
+            panic!("The analyzer started without an initialized server");
+        };
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(error.to_string().contains("did not initialize within"));
+    }
+
+    /// Log output collected in memory.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn analysis_logs_its_phases() {
+        let root = std::env::temp_dir().join(format!("analyzer-logging-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("notes.txt"), "").unwrap();
+        let (sender, receiver, _server) = lsp_client::memory::transport();
+        let analyzer = Analyzer {
+            roots: vec![root.clone()],
+            lsp_client: lsp_client::LspClient::from_transport(sender, receiver),
+            config: AnalyzerConfig::default(),
+            open_documents: Default::default(),
+        };
 
-        std::fs::write("graph.json", serde_json::to_string_pretty(&graph).unwrap()).unwrap();
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::INFO)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let analysis = analyzer.analyze().with_subscriber(subscriber).await;
+        std::fs::remove_dir_all(&root).unwrap();
 
-        Ok(graph)
+        assert_eq!(analysis.unwrap().graph.node_count(), 2);
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        for event in [
+            "Walking files",
+            "Walk finished",
+            "Requesting symbols",
+            "Symbols added",
+        ] {
+            assert!(logs.contains(event), "{event:?} missing from {logs}");
+        }
     }
 }
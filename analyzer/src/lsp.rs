@@ -1,54 +1,539 @@
+use std::collections::HashMap;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Mutex;
 
 use lsp_client::lsp_types;
 use lsp_client::{lsp_types::Uri, LspClient};
 
-use graph::{EdgeData, Graph, NodeContents, NodeData, NodeId};
+use graph::{EdgeData, Graph, NodeContents, NodeData, NodeId, Severity};
 
+use crate::{AnalyzerConfig, CancellationToken};
+
+/// Decides whether a document symbol and its children are added to the graph,
+/// given the symbol and its nesting depth, where top-level symbols have depth 0.
+pub type SymbolFilter = fn(&lsp_types::DocumentSymbol, usize) -> bool;
+
+/// The default LSP language ID for a file, based on its extension.
+pub fn language_id_for(path: &Path) -> Option<&'static str> {
+    let language_id = match path.extension()?.to_str()? {
+        "rs" => "rust",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" => "javascript",
+        "jsx" => "javascriptreact",
+        "py" => "python",
+        "go" => "go",
+        _ => return None,
+    };
+    Some(language_id)
+}
+
+/// The language ID for a file, preferring the overrides in the config.
+fn resolve_language_id<'a>(config: &'a AnalyzerConfig, path: &Path) -> Option<&'a str> {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    if let Some(language_id) = extension.and_then(|ext| config.language_ids.get(ext)) {
+        return Some(language_id);
+    }
+    language_id_for(path)
+}
+
+/// Add the document symbols of all Rust files to the graph.
+/// Returns whether population stopped early because `max_nodes` was reached.
 pub async fn populate_symbols(
     graph: &mut Graph,
     lsp_client: &LspClient,
-) -> Result<(), anyhow::Error> {
+    open_documents: &OpenDocuments,
+    config: &AnalyzerConfig,
+    cancel: &CancellationToken,
+) -> Result<bool, anyhow::Error> {
     let nodes: Vec<_> = graph.nodes().collect();
     for node_id in nodes {
+        cancel.check()?;
         let node = graph.node(node_id).unwrap();
         let graph::NodeContents::File { path, .. } = &node.contents else {
             continue;
         };
-        let path = path.to_str().unwrap();
-        let path = PathBuf::from(path);
-        let Some(ext) = path.extension() else {
+        let path = path.clone();
+        let Some(language_id) = resolve_language_id(config, &path) else {
             continue;
         };
-        if ext == "rs" {
-            populate_document_symbols(&path, node_id, graph, lsp_client).await?;
+        if !config.symbol_languages.iter().any(|l| l == language_id) {
+            continue;
+        }
+        if let Some(since) = config.since {
+            let modified = fs::metadata(&path)?.modified()?;
+            if modified <= since {
+                continue;
+            }
+        }
+        tracing::debug!(path = %path.display(), "Requesting document symbols");
+        if !open_document(
+            &path,
+            language_id,
+            lsp_client,
+            open_documents,
+            config.lossy_utf8,
+        )
+        .await?
+        {
+            continue;
+        }
+        let populate = populate_document_symbols(&path, node_id, graph, lsp_client, config);
+        if cancel.run_until_cancelled(populate).await?? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Store the moniker of every item with a known position. The requests are sent
+/// concurrently, bounded by the client's limit on in-flight requests.
+pub async fn populate_monikers(
+    graph: &mut Graph,
+    lsp_client: &LspClient,
+    cancel: &CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let items = graph.find(|node| matches!(node.contents, NodeContents::Item { .. }));
+    populate_item_monikers(graph, items, lsp_client, cancel).await
+}
+
+/// Like [`populate_monikers`], but only for `items`.
+pub(crate) async fn populate_item_monikers(
+    graph: &mut Graph,
+    items: Vec<NodeId>,
+    lsp_client: &LspClient,
+    cancel: &CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let positions: Vec<_> = items
+        .into_iter()
+        .filter_map(|item_id| Some((item_id, symbol_position(graph, item_id)?)))
+        .collect();
+    let requests = positions.into_iter().map(|(item_id, position)| async move {
+        // Items whose request fails keep their current moniker
+        let monikers = lsp_client.text_document_moniker(position).await.ok()?;
+        let moniker = monikers.into_iter().next()?;
+        Some((item_id, moniker.identifier))
+    });
+    let results = cancel
+        .run_until_cancelled(futures::future::join_all(requests))
+        .await?;
+
+    for (item_id, identifier) in results.into_iter().flatten() {
+        if let NodeContents::Item { moniker, .. } = &mut graph.node_mut(item_id).unwrap().contents {
+            *moniker = Some(identifier);
         }
     }
     Ok(())
 }
 
+/// Store the hover text of every item with a known position as its documentation.
+pub async fn populate_docs(
+    graph: &mut Graph,
+    lsp_client: &LspClient,
+    config: &AnalyzerConfig,
+    cancel: &CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let items = graph.find(|node| matches!(node.contents, NodeContents::Item { .. }));
+    populate_item_docs(graph, items, lsp_client, config, cancel).await
+}
+
+/// Like [`populate_docs`], but only for `items`.
+pub(crate) async fn populate_item_docs(
+    graph: &mut Graph,
+    items: Vec<NodeId>,
+    lsp_client: &LspClient,
+    config: &AnalyzerConfig,
+    cancel: &CancellationToken,
+) -> Result<(), anyhow::Error> {
+    for item_id in items {
+        let Some(position) = symbol_position(graph, item_id) else {
+            continue;
+        };
+        let params = lsp_types::HoverParams {
+            text_document_position_params: position,
+            work_done_progress_params: Default::default(),
+        };
+        let Some(hover) = cancel
+            .run_until_cancelled(lsp_client.hover(params))
+            .await??
+        else {
+            continue;
+        };
+        let text = hover_text(hover.contents);
+        let text = if config.raw_markdown_docs {
+            text
+        } else {
+            strip_markdown(&text)
+        };
+        if let NodeContents::Item { doc, .. } = &mut graph.node_mut(item_id).unwrap().contents {
+            *doc = Some(text);
+        }
+    }
+    Ok(())
+}
+
+/// The text of a hover response, with the parts separated by blank lines.
+fn hover_text(contents: lsp_types::HoverContents) -> String {
+    let marked = |marked: lsp_types::MarkedString| match marked {
+        lsp_types::MarkedString::String(text) => text,
+        lsp_types::MarkedString::LanguageString(code) => {
+            format!("```{}\n{}\n```", code.language, code.value)
+        }
+    };
+    match contents {
+        lsp_types::HoverContents::Scalar(text) => marked(text),
+        lsp_types::HoverContents::Array(texts) => texts
+            .into_iter()
+            .map(marked)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        lsp_types::HoverContents::Markup(markup) => markup.value,
+    }
+}
+
+/// Reduce Markdown to plain text by dropping code fences, heading markers, rules and backticks.
+fn strip_markdown(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(|line| {
+            let line = line.trim_start_matches('#').trim_start();
+            if line.chars().all(|c| c == '-') && line.len() >= 3 {
+                ""
+            } else {
+                line
+            }
+        })
+        .map(|line| line.replace('`', ""))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
 pub async fn populate_document_symbols(
     path: &Path,
     node_id: NodeId,
     graph: &mut Graph,
     lsp_client: &LspClient,
-) -> Result<(), anyhow::Error> {
+    config: &AnalyzerConfig,
+) -> Result<bool, anyhow::Error> {
     let document_symbols = retrieve_document_symbols(path, lsp_client).await?;
-    add_document_symbols(graph, node_id, document_symbols)?;
+    add_document_symbols(graph, node_id, document_symbols, config)
+}
+
+/// Request the symbols of a file again and replace the items below its node with them.
+/// Returns whether adding them stopped early because `max_nodes` was reached.
+pub async fn refresh_document_symbols(
+    graph: &mut Graph,
+    file_id: NodeId,
+    lsp_client: &LspClient,
+    open_documents: &OpenDocuments,
+    config: &AnalyzerConfig,
+) -> Result<bool, anyhow::Error> {
+    let Some(NodeContents::File { path, .. }) = graph.node(file_id).map(|node| &node.contents)
+    else {
+        return Ok(false);
+    };
+    let path = path.clone();
+    let Some(language_id) = resolve_language_id(config, &path) else {
+        return Ok(false);
+    };
+    if !config.symbol_languages.iter().any(|l| l == language_id) {
+        return Ok(false);
+    }
+    if !open_document(
+        &path,
+        language_id,
+        lsp_client,
+        open_documents,
+        config.lossy_utf8,
+    )
+    .await?
+    {
+        graph.replace_subtree(file_id, std::iter::empty());
+        return Ok(false);
+    }
+    let lsp_types::DocumentSymbolResponse::Nested(symbols) =
+        retrieve_document_symbols(&path, lsp_client).await?
+    else {
+        anyhow::bail!("Flat document symbols are not supported yet");
+    };
 
+    // The nodes left once the old items are removed
+    let remaining = graph.node_count() - graph.descendants(file_id).len();
+    let room = config
+        .max_nodes
+        .map_or(usize::MAX, |max_nodes| max_nodes.saturating_sub(remaining));
+    let symbols: Vec<_> = symbols
+        .into_iter()
+        .filter(|symbol| keeps_symbol(config, symbol, 0))
+        .collect();
+    let truncated = symbols.len() > room;
+    let (items, children): (Vec<_>, Vec<_>) = symbols
+        .into_iter()
+        .take(room)
+        .map(|mut symbol| {
+            let children = symbol.children.take().unwrap_or_default();
+            (NodeData::new(symbol_contents(symbol)), children)
+        })
+        .unzip();
+    let item_ids = graph.replace_subtree(file_id, items.into_iter());
+    for (item_id, children) in item_ids.into_iter().zip(children) {
+        for child in children {
+            if add_document_symbol(graph, item_id, child, 1, config)? {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(truncated)
+}
+
+/// The version of every document opened on the server, keyed by path.
+pub(crate) type OpenDocuments = Mutex<HashMap<PathBuf, i32>>;
+
+/// Send the contents of a file to the server, opening it on first use and replacing
+/// its contents afterwards. Returns whether the file was sent, which it is not if
+/// its contents are not valid UTF-8 and `lossy_utf8` is disabled.
+async fn open_document(
+    path: &Path,
+    language_id: &str,
+    lsp_client: &LspClient,
+    open_documents: &OpenDocuments,
+    lossy_utf8: bool,
+) -> Result<bool, anyhow::Error> {
+    let Some(text) = read_source(path, lossy_utf8)? else {
+        tracing::warn!(path = %path.display(), "Skipping file that is not valid UTF-8");
+        return Ok(false);
+    };
+    let uri = path_to_uri(path)?;
+    let version = open_documents
+        .lock()
+        .unwrap()
+        .get(path)
+        .map(|version| version + 1);
+    match version {
+        None => {
+            lsp_client
+                .did_open(lsp_types::DidOpenTextDocumentParams {
+                    text_document: lsp_types::TextDocumentItem {
+                        uri,
+                        language_id: language_id.to_owned(),
+                        version: 0,
+                        text,
+                    },
+                })
+                .await?;
+        }
+        Some(version) => {
+            lsp_client
+                .did_change(lsp_types::DidChangeTextDocumentParams {
+                    text_document: lsp_types::VersionedTextDocumentIdentifier { uri, version },
+                    content_changes: vec![lsp_types::TextDocumentContentChangeEvent {
+                        range: None,
+                        range_length: None,
+                        text,
+                    }],
+                })
+                .await?;
+        }
+    }
+    open_documents
+        .lock()
+        .unwrap()
+        .insert(path.to_owned(), version.unwrap_or(0));
+    Ok(true)
+}
+
+/// Close the documents of deleted files that were opened on the server.
+pub(crate) async fn close_documents(
+    paths: &[PathBuf],
+    lsp_client: &LspClient,
+    open_documents: &OpenDocuments,
+) -> Result<(), anyhow::Error> {
+    for path in paths {
+        if open_documents.lock().unwrap().remove(path).is_none() {
+            continue;
+        }
+        lsp_client
+            .did_close(lsp_types::DidCloseTextDocumentParams {
+                text_document: lsp_types::TextDocumentIdentifier {
+                    uri: path_to_uri(path)?,
+                },
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+/// Read a source file without its byte order mark. Invalid UTF-8 is replaced if
+/// `lossy_utf8` is set, otherwise `None` is returned.
+fn read_source(path: &Path, lossy_utf8: bool) -> Result<Option<String>, anyhow::Error> {
+    let bytes = fs::read(path)?;
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(Some(text.to_owned())),
+        Err(_) if lossy_utf8 => Ok(Some(String::from_utf8_lossy(bytes).into_owned())),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Convert an absolute file system path into a percent-encoded `file://` URI.
+pub(crate) fn path_to_uri(path: &Path) -> Result<Uri, anyhow::Error> {
+    let url = url::Url::from_file_path(path)
+        .map_err(|()| anyhow::anyhow!("Path {} is not absolute", path.display()))?;
+    Uri::from_str(url.as_str()).map_err(|e| anyhow::anyhow!("Invalid URI {}: {}", url, e))
+}
+
+/// Convert a `file://` URI into a file system path, decoding percent-encoded characters.
+pub(crate) fn uri_to_path(uri: &Uri) -> Result<PathBuf, anyhow::Error> {
+    let url = url::Url::parse(uri.as_str())?;
+    url.to_file_path()
+        .map_err(|()| anyhow::anyhow!("URI {} is not a file URI", uri.as_str()))
+}
+
+/// Flag nodes with the most severe diagnostic reported for them. Each diagnostic is
+/// attributed to the innermost item overlapping its range, or to the file otherwise.
+pub fn populate_diagnostics(
+    graph: &mut Graph,
+    diagnostics: &[lsp_types::PublishDiagnosticsParams],
+) -> Result<(), anyhow::Error> {
+    for params in diagnostics {
+        // Servers may publish diagnostics for virtual documents, which have no node
+        let Ok(path) = uri_to_path(&params.uri) else {
+            continue;
+        };
+        let Some(file_id) = graph.find_first(|node| match &node.contents {
+            NodeContents::File {
+                path: file_path, ..
+            } => *file_path == path,
+            _ => false,
+        }) else {
+            continue;
+        };
+        let items: Vec<_> = graph
+            .items_in_file(file_id)
+            .into_iter()
+            .filter_map(|item_id| match graph.node(item_id).unwrap().contents {
+                NodeContents::Item {
+                    range: Some(range), ..
+                } => Some((item_id, range)),
+                _ => None,
+            })
+            .collect();
+
+        for diagnostic in &params.diagnostics {
+            let range = to_graph_range(diagnostic.range);
+            let severity = to_graph_severity(diagnostic.severity);
+            let node_id = items
+                .iter()
+                // Later items in pre-order are nested deeper
+                .rfind(|(_, item_range)| item_range.overlaps(&range))
+                .map(|&(item_id, _)| item_id)
+                .unwrap_or(file_id);
+            let node = graph.node_mut(node_id).unwrap();
+            node.max_severity = node.max_severity.max(Some(severity));
+        }
+    }
     Ok(())
 }
 
+/// The position of an item's name, as needed by requests about a symbol such as
+/// references or hover. Falls back to the start of the item's range.
+pub fn symbol_position(graph: &Graph, id: NodeId) -> Option<lsp_types::TextDocumentPositionParams> {
+    let NodeContents::Item {
+        range,
+        selection_range,
+        ..
+    } = graph.node(id)?.contents
+    else {
+        return None;
+    };
+    let position = selection_range.or(range)?.start;
+    let NodeContents::File { path, .. } = &graph.node(graph.containing_file(id)?)?.contents else {
+        return None;
+    };
+    Some(lsp_types::TextDocumentPositionParams {
+        text_document: lsp_types::TextDocumentIdentifier {
+            uri: path_to_uri(path).ok()?,
+        },
+        position: to_lsp_position(position),
+    })
+}
+
+pub(crate) fn to_graph_range(range: lsp_types::Range) -> graph::Range {
+    graph::Range {
+        start: to_graph_position(range.start),
+        end: to_graph_position(range.end),
+    }
+}
+
+fn to_graph_position(position: lsp_types::Position) -> graph::Position {
+    graph::Position {
+        line: position.line,
+        character: position.character,
+    }
+}
+
+fn to_lsp_position(position: graph::Position) -> lsp_types::Position {
+    lsp_types::Position {
+        line: position.line,
+        character: position.character,
+    }
+}
+
+fn to_graph_symbol_kind(kind: lsp_types::SymbolKind) -> Option<graph::SymbolKind> {
+    use graph::SymbolKind as Kind;
+    use lsp_types::SymbolKind as LspKind;
+
+    let kind = match kind {
+        LspKind::FILE => Kind::File,
+        LspKind::MODULE => Kind::Module,
+        LspKind::NAMESPACE => Kind::Namespace,
+        LspKind::PACKAGE => Kind::Package,
+        LspKind::CLASS => Kind::Class,
+        LspKind::METHOD => Kind::Method,
+        LspKind::PROPERTY => Kind::Property,
+        LspKind::FIELD => Kind::Field,
+        LspKind::CONSTRUCTOR => Kind::Constructor,
+        LspKind::ENUM => Kind::Enum,
+        LspKind::INTERFACE => Kind::Interface,
+        LspKind::FUNCTION => Kind::Function,
+        LspKind::VARIABLE => Kind::Variable,
+        LspKind::CONSTANT => Kind::Constant,
+        LspKind::STRING => Kind::String,
+        LspKind::NUMBER => Kind::Number,
+        LspKind::BOOLEAN => Kind::Boolean,
+        LspKind::ARRAY => Kind::Array,
+        LspKind::OBJECT => Kind::Object,
+        LspKind::KEY => Kind::Key,
+        LspKind::NULL => Kind::Null,
+        LspKind::ENUM_MEMBER => Kind::EnumMember,
+        LspKind::STRUCT => Kind::Struct,
+        LspKind::EVENT => Kind::Event,
+        LspKind::OPERATOR => Kind::Operator,
+        LspKind::TYPE_PARAMETER => Kind::TypeParameter,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+fn to_graph_severity(severity: Option<lsp_types::DiagnosticSeverity>) -> Severity {
+    match severity {
+        Some(lsp_types::DiagnosticSeverity::WARNING) => Severity::Warning,
+        Some(lsp_types::DiagnosticSeverity::INFORMATION) => Severity::Information,
+        Some(lsp_types::DiagnosticSeverity::HINT) => Severity::Hint,
+        // Clients may interpret a missing severity, treat it as an error to be safe
+        _ => Severity::Error,
+    }
+}
+
 async fn retrieve_document_symbols(
     path: &Path,
     lsp_client: &LspClient,
 ) -> Result<lsp_types::DocumentSymbolResponse, anyhow::Error> {
-    let uri = Uri::from_str(&format!(
-        "file://{}",
-        path.to_str()
-            .ok_or_else(|| anyhow::anyhow!("Failed to convert path to string"))?
-    ))?;
+    let uri = path_to_uri(path)?;
 
     lsp_client
         .document_symbol(lsp_client::lsp_types::DocumentSymbolParams {
@@ -63,29 +548,39 @@ fn add_document_symbols(
     graph: &mut Graph,
     file_id: NodeId,
     document_symbols: lsp_types::DocumentSymbolResponse,
-) -> Result<(), anyhow::Error> {
+    config: &AnalyzerConfig,
+) -> Result<bool, anyhow::Error> {
     let lsp_types::DocumentSymbolResponse::Nested(symbols) = document_symbols else {
         anyhow::bail!("Flat document symbols are not supported yet");
     };
 
     for symbol in symbols {
-        add_document_symbol(graph, file_id, symbol)?;
+        if add_document_symbol(graph, file_id, symbol, 0, config)? {
+            return Ok(true);
+        }
     }
 
-    Ok(())
+    Ok(false)
 }
 
 fn add_document_symbol(
     graph: &mut Graph,
     parent_id: NodeId,
-    symbol: lsp_types::DocumentSymbol,
-) -> Result<(), anyhow::Error> {
-    let contents = NodeContents::Item {
-        display_name: symbol.name,
-        moniker: None,
-    };
-    let node = NodeData { contents };
-    let item_id = graph.add_node(node);
+    mut symbol: lsp_types::DocumentSymbol,
+    depth: usize,
+    config: &AnalyzerConfig,
+) -> Result<bool, anyhow::Error> {
+    if !keeps_symbol(config, &symbol, depth) {
+        return Ok(false);
+    }
+    if config
+        .max_nodes
+        .is_some_and(|max_nodes| graph.node_count() >= max_nodes)
+    {
+        return Ok(true);
+    }
+    let children = symbol.children.take().unwrap_or_default();
+    let item_id = graph.add_node(NodeData::new(symbol_contents(symbol)));
     let edge = EdgeData {
         from: parent_id,
         to: item_id,
@@ -93,9 +588,478 @@ fn add_document_symbol(
     };
     graph.add_edge(edge);
 
-    for child in symbol.children.unwrap_or_default() {
-        add_document_symbol(graph, item_id, child)?;
+    for child in children {
+        if add_document_symbol(graph, item_id, child, depth + 1, config)? {
+            return Ok(true);
+        }
     }
 
-    Ok(())
+    Ok(false)
+}
+
+/// Whether `config.symbol_filter` lets the symbol at `depth` into the graph.
+fn keeps_symbol(config: &AnalyzerConfig, symbol: &lsp_types::DocumentSymbol, depth: usize) -> bool {
+    config
+        .symbol_filter
+        .is_none_or(|symbol_filter| symbol_filter(symbol, depth))
+}
+
+/// The item for a document symbol, without its children.
+fn symbol_contents(symbol: lsp_types::DocumentSymbol) -> NodeContents {
+    NodeContents::Item {
+        display_name: symbol.name,
+        moniker: None,
+        range: Some(to_graph_range(symbol.range)),
+        selection_range: Some(to_graph_range(symbol.selection_range)),
+        doc: None,
+        kind: to_graph_symbol_kind(symbol.kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn path_to_uri_percent_encodes_spaces() {
+        let uri = path_to_uri(Path::new("/home/user/my project/src/main.rs")).unwrap();
+        assert_eq!(uri.as_str(), "file:///home/user/my%20project/src/main.rs");
+    }
+
+    #[test]
+    fn path_to_uri_rejects_relative_paths() {
+        assert!(path_to_uri(Path::new("src/main.rs")).is_err());
+    }
+
+    #[test]
+    fn positions_round_trip_between_lsp_and_graph() {
+        let position = lsp_types::Position::new(3, 14);
+        assert_eq!(to_lsp_position(to_graph_position(position)), position);
+
+        let range = to_graph_range(lsp_types::Range::new(
+            position,
+            lsp_types::Position::new(4, 0),
+        ));
+        assert_eq!((range.start.line, range.start.character), (3, 14));
+        assert_eq!((range.end.line, range.end.character), (4, 0));
+    }
+
+    #[allow(deprecated)]
+    fn symbol(name: &str, children: Vec<lsp_types::DocumentSymbol>) -> lsp_types::DocumentSymbol {
+        lsp_types::DocumentSymbol {
+            name: name.to_owned(),
+            detail: None,
+            kind: lsp_types::SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            range: lsp_types::Range::default(),
+            selection_range: lsp_types::Range::default(),
+            children: Some(children),
+        }
+    }
+
+    fn add_file(graph: &mut Graph) -> NodeId {
+        graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "lib.rs".to_owned(),
+            path: PathBuf::from("/src/lib.rs"),
+        }))
+    }
+
+    #[test]
+    fn document_symbols_stop_at_max_nodes() {
+        let symbols = || {
+            lsp_types::DocumentSymbolResponse::Nested(vec![
+                symbol("a", vec![symbol("a1", vec![]), symbol("a2", vec![])]),
+                symbol("b", vec![]),
+            ])
+        };
+
+        let mut graph = Graph::default();
+        let file = add_file(&mut graph);
+        assert!(
+            !add_document_symbols(&mut graph, file, symbols(), &AnalyzerConfig::default()).unwrap()
+        );
+        assert_eq!(graph.node_count(), 5);
+
+        let mut graph = Graph::default();
+        let file = add_file(&mut graph);
+        let config = AnalyzerConfig {
+            max_nodes: Some(3),
+            ..AnalyzerConfig::default()
+        };
+        assert!(add_document_symbols(&mut graph, file, symbols(), &config).unwrap());
+        assert_eq!(graph.node_count(), 3);
+    }
+
+    #[test]
+    fn filtered_symbols_are_left_out_with_their_children() {
+        let symbols = lsp_types::DocumentSymbolResponse::Nested(vec![
+            symbol("tests", vec![symbol("it_works", vec![])]),
+            symbol("run", vec![symbol("tests", vec![])]),
+        ]);
+        let config = AnalyzerConfig {
+            symbol_filter: Some(|symbol, depth| depth > 0 || symbol.name != "tests"),
+            ..AnalyzerConfig::default()
+        };
+
+        let mut graph = Graph::default();
+        let file = add_file(&mut graph);
+        assert!(!add_document_symbols(&mut graph, file, symbols, &config).unwrap());
+        let mut names: Vec<_> = graph
+            .items()
+            .map(|(_, node)| node.contents.display_name())
+            .collect();
+        names.sort_unstable();
+        assert_eq!(names, ["run", "tests"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn uri_to_path_decodes_percent_encoding() {
+        let path = Path::new("/home/user/my project/src/main.rs");
+        let uri = path_to_uri(path).unwrap();
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+
+        let uri = Uri::from_str("untitled:Untitled-1").unwrap();
+        assert!(uri_to_path(&uri).is_err());
+    }
+
+    fn diagnostic(line: u32, severity: lsp_types::DiagnosticSeverity) -> lsp_types::Diagnostic {
+        let position = lsp_types::Position { line, character: 0 };
+        lsp_types::Diagnostic {
+            range: lsp_types::Range::new(position, position),
+            severity: Some(severity),
+            ..lsp_types::Diagnostic::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn diagnostics_flag_the_innermost_item() {
+        let mut graph = Graph::default();
+        let file = add_file(&mut graph);
+        let lines = |start, end| lsp_types::Range {
+            start: lsp_types::Position::new(start, 0),
+            end: lsp_types::Position::new(end, 0),
+        };
+        let mut module = symbol("module", vec![symbol("function", vec![])]);
+        module.range = lines(0, 10);
+        module.children.as_mut().unwrap()[0].range = lines(2, 4);
+        add_document_symbols(
+            &mut graph,
+            file,
+            lsp_types::DocumentSymbolResponse::Nested(vec![module]),
+            &AnalyzerConfig::default(),
+        )
+        .unwrap();
+        let [module, function] = graph.items_in_file(file)[..] else {
+            panic!("Expected two items");
+        };
+
+        let diagnostics = [
+            lsp_types::PublishDiagnosticsParams {
+                uri: path_to_uri(Path::new("/src/lib.rs")).unwrap(),
+                diagnostics: vec![
+                    diagnostic(3, lsp_types::DiagnosticSeverity::WARNING),
+                    diagnostic(3, lsp_types::DiagnosticSeverity::ERROR),
+                    diagnostic(8, lsp_types::DiagnosticSeverity::HINT),
+                    diagnostic(20, lsp_types::DiagnosticSeverity::INFORMATION),
+                ],
+                version: None,
+            },
+            lsp_types::PublishDiagnosticsParams {
+                uri: Uri::from_str("untitled:Untitled-1").unwrap(),
+                diagnostics: vec![diagnostic(0, lsp_types::DiagnosticSeverity::ERROR)],
+                version: None,
+            },
+        ];
+        populate_diagnostics(&mut graph, &diagnostics).unwrap();
+
+        let severity = |id: NodeId| graph.node(id).unwrap().max_severity;
+        assert_eq!(severity(function), Some(Severity::Error));
+        assert_eq!(severity(module), Some(Severity::Hint));
+        assert_eq!(severity(file), Some(Severity::Information));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symbol_position_points_at_the_name() {
+        let mut graph = Graph::default();
+        let file = add_file(&mut graph);
+        let mut function = symbol("function", vec![]);
+        function.range = lsp_types::Range::new(
+            lsp_types::Position::new(2, 0),
+            lsp_types::Position::new(4, 1),
+        );
+        function.selection_range = lsp_types::Range::new(
+            lsp_types::Position::new(2, 7),
+            lsp_types::Position::new(2, 15),
+        );
+        add_document_symbols(
+            &mut graph,
+            file,
+            lsp_types::DocumentSymbolResponse::Nested(vec![function]),
+            &AnalyzerConfig::default(),
+        )
+        .unwrap();
+        let [function] = graph.items_in_file(file)[..] else {
+            panic!("Expected one item");
+        };
+
+        let params = symbol_position(&graph, function).unwrap();
+        assert_eq!(
+            params.text_document.uri,
+            path_to_uri(Path::new("/src/lib.rs")).unwrap()
+        );
+        assert_eq!(params.position, lsp_types::Position::new(2, 7));
+        assert!(symbol_position(&graph, file).is_none());
+    }
+
+    #[test]
+    fn language_id_prefers_configured_overrides() {
+        let mut config = AnalyzerConfig::default();
+        config
+            .language_ids
+            .insert("mjs".to_owned(), "javascript".to_owned());
+        config
+            .language_ids
+            .insert("rs".to_owned(), "custom-rust".to_owned());
+
+        assert_eq!(
+            language_id_for(Path::new("src/main.ts")),
+            Some("typescript")
+        );
+        assert_eq!(language_id_for(Path::new("Makefile")), None);
+        assert_eq!(
+            resolve_language_id(&config, Path::new("index.mjs")),
+            Some("javascript")
+        );
+        assert_eq!(
+            resolve_language_id(&config, Path::new("lib.rs")),
+            Some("custom-rust")
+        );
+        assert_eq!(
+            resolve_language_id(&config, Path::new("main.py")),
+            Some("python")
+        );
+        assert_eq!(resolve_language_id(&config, Path::new("README")), None);
+    }
+
+    #[test]
+    fn symbol_kinds_map_to_graph_kinds() {
+        assert_eq!(
+            to_graph_symbol_kind(lsp_types::SymbolKind::STRUCT),
+            Some(graph::SymbolKind::Struct)
+        );
+        assert_eq!(
+            to_graph_symbol_kind(lsp_types::SymbolKind::TYPE_PARAMETER),
+            Some(graph::SymbolKind::TypeParameter)
+        );
+        let unknown: lsp_types::SymbolKind = serde_json::from_value(serde_json::json!(99)).unwrap();
+        assert_eq!(to_graph_symbol_kind(unknown), None);
+    }
+
+    #[test]
+    fn read_source_strips_the_bom_and_handles_invalid_utf8() {
+        let dir = std::env::temp_dir().join(format!("analyzer-read-source-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bom = dir.join("bom.rs");
+        fs::write(&bom, b"\xEF\xBB\xBFfn main() {}").unwrap();
+        let invalid = dir.join("invalid.rs");
+        fs::write(&invalid, b"// \xFF\n").unwrap();
+
+        let with_bom = read_source(&bom, false);
+        let strict = read_source(&invalid, false);
+        let lossy = read_source(&invalid, true);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(with_bom.unwrap().unwrap(), "fn main() {}");
+        assert_eq!(strict.unwrap(), None);
+        assert_eq!(lossy.unwrap().unwrap(), "// \u{FFFD}\n");
+    }
+
+    #[tokio::test]
+    async fn files_not_modified_since_are_not_queried() {
+        let dir = std::env::temp_dir().join(format!("analyzer-since-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lib.rs");
+        fs::write(&path, "fn main() {}\n").unwrap();
+        let mut graph = Graph::default();
+        graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "lib.rs".to_owned(),
+            path: path.clone(),
+        }));
+
+        let (sender, receiver, mut server) = lsp_client::memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let future = AnalyzerConfig {
+            since: Some(SystemTime::now() + Duration::from_secs(3600)),
+            ..AnalyzerConfig::default()
+        };
+        let cancel = CancellationToken::new();
+        let open_documents = OpenDocuments::default();
+        let truncated =
+            populate_symbols(&mut graph, &client, &open_documents, &future, &cancel).await;
+        let unmodified_messages = server.incoming.try_recv();
+
+        let range = serde_json::json!({
+            "start": {"line": 0, "character": 0},
+            "end": {"line": 0, "character": 12},
+        });
+        let symbols = serde_json::json!([
+            {"name": "main", "kind": 12, "range": range, "selectionRange": range},
+        ]);
+        let responder = tokio::spawn(async move {
+            let opened = server.incoming.recv().await.unwrap();
+            let request: serde_json::Value =
+                serde_json::from_str(&server.incoming.recv().await.unwrap()).unwrap();
+            let response =
+                serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": symbols});
+            server.outgoing.send(response.to_string()).unwrap();
+            (opened, request)
+        });
+        let past = AnalyzerConfig {
+            since: Some(SystemTime::UNIX_EPOCH),
+            ..AnalyzerConfig::default()
+        };
+        let modified = populate_symbols(&mut graph, &client, &open_documents, &past, &cancel).await;
+        let (opened, request) = responder.await.unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!truncated.unwrap());
+        assert!(unmodified_messages.is_err());
+        assert!(!modified.unwrap());
+        assert_eq!(graph.node_count(), 2);
+        assert!(opened.contains("textDocument/didOpen"));
+        assert_eq!(request["method"], "textDocument/documentSymbol");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hover_markup_is_stored_as_the_doc() {
+        let mut graph = Graph::default();
+        let file = add_file(&mut graph);
+        let mut function = symbol("main", vec![]);
+        function.selection_range = lsp_types::Range::new(
+            lsp_types::Position::new(0, 3),
+            lsp_types::Position::new(0, 7),
+        );
+        add_document_symbols(
+            &mut graph,
+            file,
+            lsp_types::DocumentSymbolResponse::Nested(vec![function]),
+            &AnalyzerConfig::default(),
+        )
+        .unwrap();
+        let [function] = graph.items_in_file(file)[..] else {
+            panic!("Expected one item");
+        };
+
+        let (sender, receiver, mut server) = lsp_client::memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let markup = "```rust\nfn main()\n```\nThe `main` entry point.";
+        let responder = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let request: serde_json::Value =
+                    serde_json::from_str(&server.incoming.recv().await.unwrap()).unwrap();
+                let hover = serde_json::json!({"contents": {"kind": "markdown", "value": markup}});
+                let response =
+                    serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": hover});
+                server.outgoing.send(response.to_string()).unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        let cancel = CancellationToken::new();
+        let doc = |graph: &Graph| match &graph.node(function).unwrap().contents {
+            NodeContents::Item { doc, .. } => doc.clone(),
+            _ => unreachable!(),
+        };
+        populate_docs(&mut graph, &client, &AnalyzerConfig::default(), &cancel)
+            .await
+            .unwrap();
+        let stripped = doc(&graph);
+        let raw_config = AnalyzerConfig {
+            raw_markdown_docs: true,
+            ..AnalyzerConfig::default()
+        };
+        populate_docs(&mut graph, &client, &raw_config, &cancel)
+            .await
+            .unwrap();
+        let raw = doc(&graph);
+        let requests = responder.await.unwrap();
+
+        assert_eq!(requests[0]["method"], "textDocument/hover");
+        assert_eq!(
+            requests[0]["params"]["position"],
+            serde_json::json!({"line": 0, "character": 3})
+        );
+        assert_eq!(
+            stripped.as_deref(),
+            Some("fn main()\nThe main entry point.")
+        );
+        assert_eq!(raw.as_deref(), Some(markup));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn monikers_are_stored_for_the_items_that_have_one() {
+        let mut graph = Graph::default();
+        let file = add_file(&mut graph);
+        let symbols = ["exported", "local", "failing"].map(|name| symbol(name, vec![]));
+        let symbols = symbols
+            .into_iter()
+            .enumerate()
+            .map(|(line, mut symbol)| {
+                let start = lsp_types::Position::new(line as u32, 0);
+                symbol.selection_range = lsp_types::Range::new(start, start);
+                symbol
+            })
+            .collect();
+        add_document_symbols(
+            &mut graph,
+            file,
+            lsp_types::DocumentSymbolResponse::Nested(symbols),
+            &AnalyzerConfig::default(),
+        )
+        .unwrap();
+
+        let (sender, receiver, mut server) = lsp_client::memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let responder = tokio::spawn(async move {
+            for _ in 0..3 {
+                let request: serde_json::Value =
+                    serde_json::from_str(&server.incoming.recv().await.unwrap()).unwrap();
+                let id = &request["id"];
+                let response = match request["params"]["position"]["line"].as_u64() {
+                    Some(0) => serde_json::json!({"jsonrpc": "2.0", "id": id, "result": [
+                        {"scheme": "rust", "identifier": "crate::exported", "unique": "scheme"},
+                    ]}),
+                    Some(1) => serde_json::json!({"jsonrpc": "2.0", "id": id, "result": null}),
+                    _ => serde_json::json!({"jsonrpc": "2.0", "id": id, "error": {
+                        "code": -32603, "message": "no moniker",
+                    }}),
+                };
+                server.outgoing.send(response.to_string()).unwrap();
+            }
+        });
+        populate_monikers(&mut graph, &client, &CancellationToken::new())
+            .await
+            .unwrap();
+        responder.await.unwrap();
+
+        let monikers: Vec<_> = graph
+            .items_in_file(file)
+            .into_iter()
+            .map(|id| match &graph.node(id).unwrap().contents {
+                NodeContents::Item { moniker, .. } => moniker.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(monikers, [Some("crate::exported".to_owned()), None, None]);
+    }
 }
@@ -1,8 +1,154 @@
-use analyzer::Analyzer;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use analyzer::{Analyzer, FileChanges};
+use graph::Graph;
+use tracing_subscriber::EnvFilter;
+
+/// How often watch mode checks the files for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long changed files must stay untouched before watch mode analyzes them.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// How long watch mode waits for files to settle before analyzing them anyway.
+const MAX_DEBOUNCE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Json,
+    D3,
+    GraphMl,
+    Dot,
+}
 
 #[tokio::main]
-async fn main() {
-    let a = Analyzer::start().await.unwrap();
-    a.graph().await.unwrap();
-    a.stop().await.unwrap();
+async fn main() -> Result<(), anyhow::Error> {
+    // Log warnings unless configured otherwise with RUST_LOG, e.g. RUST_LOG=analyzer=debug
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let mut output = PathBuf::from("graph.json");
+    let mut format = Format::Json;
+    let mut watch = false;
+    let mut relative_paths = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let Some(path) = args.next() else {
+                    anyhow::bail!("Missing path after {}", arg);
+                };
+                output = path.into();
+            }
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("json") => Format::Json,
+                    Some("d3") => Format::D3,
+                    Some("graphml") => Format::GraphMl,
+                    Some("dot") => Format::Dot,
+                    other => anyhow::bail!("Unknown format {:?}", other),
+                };
+            }
+            "--watch" => watch = true,
+            "--relative-paths" => relative_paths = true,
+            _ => anyhow::bail!("Unknown argument {}", arg),
+        }
+    }
+
+    // Paths are stored relative to the analyzed directory if requested
+    let path_root = if relative_paths {
+        Some(std::env::current_dir()?)
+    } else {
+        None
+    };
+
+    let a = Analyzer::start().await?;
+    let mut snapshot = a.snapshot()?;
+    let mut graph = a.graph().await?;
+    write_output(&mut graph, path_root.as_deref(), format, &output)?;
+
+    if watch {
+        // Ctrl-C stops watching at any point, including while waiting or updating
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            let iteration = async {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                let mut current = a.snapshot()?;
+                if current == snapshot {
+                    return Ok(());
+                }
+                // Wait for the files to settle, e.g. while an editor saves several of them
+                let deadline = tokio::time::Instant::now() + MAX_DEBOUNCE;
+                while tokio::time::Instant::now() < deadline {
+                    tokio::time::sleep(DEBOUNCE).await;
+                    let settled = a.snapshot()?;
+                    if settled == current {
+                        break;
+                    }
+                    current = settled;
+                }
+
+                let changes = FileChanges::between(&snapshot, &current);
+                snapshot = current;
+                a.update(&mut graph, &changes).await?;
+                write_output(&mut graph, path_root.as_deref(), format, &output)?;
+                eprintln!(
+                    "Updated {}: {}, {} nodes",
+                    output.display(),
+                    changes,
+                    graph.node_count()
+                );
+                Ok::<_, anyhow::Error>(())
+            };
+            tokio::select! {
+                _ = &mut ctrl_c => break,
+                result = iteration => result?,
+            }
+        }
+    }
+
+    a.stop().await?;
+    if watch {
+        write_output(&mut graph, path_root.as_deref(), format, &output)?;
+    }
+    Ok(())
+}
+
+/// Write the graph with its paths relative to `path_root`, if given. The paths are made
+/// absolute again afterwards, since watch mode matches changed files to nodes by path.
+fn write_output(
+    graph: &mut Graph,
+    path_root: Option<&Path>,
+    format: Format,
+    output: &Path,
+) -> Result<(), anyhow::Error> {
+    let Some(path_root) = path_root else {
+        return write_graph(graph, format, output);
+    };
+    graph.make_paths_relative(path_root);
+    let result = write_graph(graph, format, output);
+    graph.make_paths_absolute(path_root);
+    result
+}
+
+fn write_graph(graph: &Graph, format: Format, output: &Path) -> Result<(), anyhow::Error> {
+    let text = match format {
+        Format::Json => return analyzer::write_json_to(graph, output),
+        Format::D3 => serde_json::to_string_pretty(&graph.to_d3_json())?,
+        Format::GraphMl => graph.to_graphml(),
+        Format::Dot => graph.to_dot(),
+    };
+    if output == Path::new("-") {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()?;
+    } else {
+        std::fs::write(output, text)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use graph::{EdgeData, Graph, NodeContents, NodeId, Relation, SymbolKind};
+
+use crate::snippet::{self, FileCache};
+
+/// Connect every Rust `mod foo;` declaration to the file holding the module's contents.
+pub fn populate_module_files(graph: &mut Graph) {
+    let mut cache = FileCache::new();
+    let modules: Vec<_> = graph
+        .find(|node| {
+            matches!(
+                node.contents,
+                NodeContents::Item {
+                    kind: Some(SymbolKind::Module),
+                    ..
+                }
+            )
+        })
+        .into_iter()
+        .filter(|&id| is_declaration(graph, id, &mut cache))
+        .collect();
+
+    for module_id in modules {
+        let Some(file_id) = module_file_candidates(graph, module_id)
+            .into_iter()
+            .find_map(|path| graph.node_by_path(&path))
+        else {
+            continue;
+        };
+        graph.add_edge_deduped(EdgeData {
+            from: module_id,
+            to: file_id,
+            relation: Relation::DeclaresModule,
+        });
+    }
+}
+
+/// Whether a module item is declared without a body, as in `mod foo;`.
+fn is_declaration(graph: &Graph, id: NodeId, cache: &mut FileCache) -> bool {
+    snippet::node_snippet(graph, id, cache).is_some_and(|text| text.trim_end().ends_with(';'))
+}
+
+/// The paths `foo.rs` and `foo/mod.rs` a module declaration may refer to,
+/// relative to the directory of the declaring file and its inline modules.
+fn module_file_candidates(graph: &Graph, module_id: NodeId) -> Vec<PathBuf> {
+    let mut inline_modules = Vec::new();
+    let mut file_path = None;
+    for ancestor in graph.ancestors(module_id) {
+        match &graph.node(ancestor).unwrap().contents {
+            NodeContents::Item {
+                display_name,
+                kind: Some(SymbolKind::Module),
+                ..
+            } => inline_modules.push(module_name(display_name)),
+            NodeContents::File { path, .. } => {
+                file_path = Some(path);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let Some(file_path) = file_path else {
+        return Vec::new();
+    };
+
+    let mut dir = module_dir(file_path);
+    dir.extend(inline_modules.into_iter().rev());
+    let name = module_name(graph.node(module_id).unwrap().contents.display_name());
+    vec![
+        dir.join(format!("{}.rs", name)),
+        dir.join(name).join("mod.rs"),
+    ]
+}
+
+/// The directory holding the files of modules declared at the top level of a file.
+fn module_dir(file_path: &Path) -> PathBuf {
+    let dir = file_path.parent().unwrap_or(Path::new(""));
+    match file_path.file_stem().and_then(|stem| stem.to_str()) {
+        Some("lib" | "main" | "mod") | None => dir.to_owned(),
+        Some(stem) => dir.join(stem),
+    }
+}
+
+fn module_name(display_name: &str) -> &str {
+    display_name.strip_prefix("r#").unwrap_or(display_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use graph::{NodeData, Position, Range};
+
+    use super::*;
+
+    fn add_child(graph: &mut Graph, parent: NodeId, contents: NodeContents) -> NodeId {
+        let child = graph.add_node(NodeData::new(contents));
+        graph.add_edge(EdgeData {
+            from: parent,
+            to: child,
+            relation: Relation::IsParentOf,
+        });
+        child
+    }
+
+    fn file(path: PathBuf) -> NodeContents {
+        NodeContents::File {
+            display_name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            path,
+        }
+    }
+
+    fn module(name: &str, start: u32, end: u32) -> NodeContents {
+        NodeContents::Item {
+            display_name: name.to_owned(),
+            moniker: None,
+            range: Some(Range {
+                start: Position {
+                    line: start,
+                    character: 0,
+                },
+                end: Position {
+                    line: end,
+                    character: 1,
+                },
+            }),
+            selection_range: None,
+            doc: None,
+            kind: Some(SymbolKind::Module),
+        }
+    }
+
+    #[test]
+    fn module_dir_nests_below_non_root_files() {
+        assert_eq!(module_dir(Path::new("src/lib.rs")), Path::new("src"));
+        assert_eq!(module_dir(Path::new("src/a/mod.rs")), Path::new("src/a"));
+        assert_eq!(module_dir(Path::new("src/a.rs")), Path::new("src/a"));
+    }
+
+    #[test]
+    fn declarations_link_to_their_files() {
+        let dir = std::env::temp_dir().join(format!("analyzer-modules-{}", std::process::id()));
+        fs::create_dir_all(dir.join("inline")).unwrap();
+        let lib = dir.join("lib.rs");
+        fs::write(&lib, "mod foo;\nmod inline {\n    mod r#bar;\n}\n").unwrap();
+
+        let mut graph = Graph::default();
+        let root = graph.add_node(NodeData::new(NodeContents::Folder {
+            display_name: "src".to_owned(),
+            path: dir.clone(),
+        }));
+        let lib = add_child(&mut graph, root, file(lib));
+        let foo_file = add_child(&mut graph, root, file(dir.join("foo.rs")));
+        let bar_file = add_child(&mut graph, root, file(dir.join("inline/bar.rs")));
+        let foo = add_child(&mut graph, lib, module("foo", 0, 0));
+        let inline = add_child(&mut graph, lib, module("inline", 1, 3));
+        let bar = add_child(&mut graph, inline, module("r#bar", 2, 2));
+        populate_module_files(&mut graph);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(graph.has_edge(foo, foo_file, Relation::DeclaresModule));
+        assert!(graph.has_edge(bar, bar_file, Relation::DeclaresModule));
+        let declared: Vec<_> = graph.edges_of_relation(Relation::DeclaresModule).collect();
+        assert_eq!(declared.len(), 2);
+        assert!(!graph.has_edge(inline, bar_file, Relation::DeclaresModule));
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+
+/// The command used to start a language server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerSpec {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl ServerSpec {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_owned(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    pub fn rust_analyzer() -> Self {
+        Self::new("rust-analyzer", &[])
+    }
+}
+
+/// Marker files identifying a project type, with the server to use for it.
+const MARKERS: &[(&str, &str, &[&str])] = &[
+    ("Cargo.toml", "rust-analyzer", &[]),
+    ("package.json", "typescript-language-server", &["--stdio"]),
+    ("go.mod", "gopls", &[]),
+];
+
+/// The language server for the project at `path`, based on the marker files it contains.
+pub fn detect_server(path: &Path) -> Option<ServerSpec> {
+    MARKERS
+        .iter()
+        .find(|(marker, _, _)| path.join(marker).is_file())
+        .map(|(_, program, args)| ServerSpec::new(program, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn servers_are_detected_by_the_first_marker() {
+        let dir = std::env::temp_dir().join(format!("analyzer-detect-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let empty = detect_server(&dir);
+        fs::write(dir.join("package.json"), "{}").unwrap();
+        let node = detect_server(&dir);
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        let rust = detect_server(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(empty, None);
+        assert_eq!(
+            node,
+            Some(ServerSpec::new("typescript-language-server", &["--stdio"]))
+        );
+        assert_eq!(rust, Some(ServerSpec::rust_analyzer()));
+    }
+}
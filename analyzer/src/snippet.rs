@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use graph::{Graph, NodeContents, NodeId};
+
+/// File contents read so far, `None` for unreadable files.
+pub(crate) type FileCache = HashMap<PathBuf, Option<String>>;
+
+/// The source lines covered by the range of an item node.
+pub(crate) fn node_snippet(graph: &Graph, id: NodeId, cache: &mut FileCache) -> Option<String> {
+    let NodeContents::Item {
+        range: Some(range), ..
+    } = graph.node(id)?.contents
+    else {
+        return None;
+    };
+    let file_id = graph.containing_file(id)?;
+    let NodeContents::File { path, .. } = &graph.node(file_id)?.contents else {
+        return None;
+    };
+    let text = cache
+        .entry(path.clone())
+        .or_insert_with(|| fs::read_to_string(path).ok())
+        .as_deref()?;
+
+    let start = range.start.line as usize;
+    let len = (range.end.line as usize).checked_sub(start)? + 1;
+    let lines: Vec<_> = text.lines().skip(start).take(len).collect();
+    if lines.is_empty() {
+        return None;
+    }
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::{EdgeData, NodeData, Position, Range, Relation};
+
+    use super::*;
+
+    fn add_item(graph: &mut Graph, file: NodeId, lines: Option<(u32, u32)>) -> NodeId {
+        let range = lines.map(|(start, end)| Range {
+            start: Position {
+                line: start,
+                character: 0,
+            },
+            end: Position {
+                line: end,
+                character: 1,
+            },
+        });
+        let item = graph.add_node(NodeData::new(NodeContents::Item {
+            display_name: "item".to_owned(),
+            moniker: None,
+            range,
+            selection_range: None,
+            doc: None,
+            kind: None,
+        }));
+        graph.add_edge(EdgeData {
+            from: file,
+            to: item,
+            relation: Relation::IsParentOf,
+        });
+        item
+    }
+
+    #[test]
+    fn snippet_covers_the_item_lines() {
+        let dir = std::env::temp_dir().join(format!("analyzer-snippet-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lib.rs");
+        fs::write(&path, "// lib\nfn main() {\n}\n").unwrap();
+
+        let mut graph = Graph::default();
+        let file = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "lib.rs".to_owned(),
+            path,
+        }));
+        let function = add_item(&mut graph, file, Some((1, 2)));
+        let unranged = add_item(&mut graph, file, None);
+        let beyond_the_end = add_item(&mut graph, file, Some((5, 6)));
+        let mut cache = FileCache::new();
+        let snippets: Vec<_> = [function, unranged, beyond_the_end, file]
+            .into_iter()
+            .map(|id| node_snippet(&graph, id, &mut cache))
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            snippets,
+            vec![Some("fn main() {\n}".to_owned()), None, None, None]
+        );
+        assert_eq!(cache.len(), 1);
+    }
+}
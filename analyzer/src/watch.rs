@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use lsp_client::lsp_types::{FileChangeType, FileEvent};
+
+use crate::file_structure::modification_times;
+use crate::lsp::path_to_uri;
+use crate::AnalyzerConfig;
+
+/// The modification time of every file below the analyzed roots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    times: HashMap<PathBuf, SystemTime>,
+}
+
+impl Snapshot {
+    pub(crate) fn take(roots: &[PathBuf], config: &AnalyzerConfig) -> Result<Self, anyhow::Error> {
+        let mut times = HashMap::new();
+        for root in roots {
+            times.extend(modification_times(root, config)?);
+        }
+        Ok(Self { times })
+    }
+}
+
+/// Files created, modified or deleted between two snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct FileChanges {
+    pub created: Vec<PathBuf>,
+    pub changed: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
+impl FileChanges {
+    pub fn between(old: &Snapshot, new: &Snapshot) -> Self {
+        let mut changes = Self::default();
+        for (path, modified) in &new.times {
+            match old.times.get(path) {
+                None => changes.created.push(path.clone()),
+                Some(old_modified) if old_modified != modified => {
+                    changes.changed.push(path.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        changes.deleted = old
+            .times
+            .keys()
+            .filter(|path| !new.times.contains_key(*path))
+            .cloned()
+            .collect();
+        changes.created.sort_unstable();
+        changes.changed.sort_unstable();
+        changes.deleted.sort_unstable();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.changed.is_empty() && self.deleted.is_empty()
+    }
+
+    /// The changes as events for `workspace/didChangeWatchedFiles`.
+    pub(crate) fn file_events(&self) -> Result<Vec<FileEvent>, anyhow::Error> {
+        let events = [
+            (&self.created, FileChangeType::CREATED),
+            (&self.changed, FileChangeType::CHANGED),
+            (&self.deleted, FileChangeType::DELETED),
+        ];
+        events
+            .into_iter()
+            .flat_map(|(paths, typ)| {
+                paths
+                    .iter()
+                    .map(move |path| Ok(FileEvent::new(path_to_uri(path)?, typ)))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for FileChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} created, {} changed, {} deleted",
+            self.created.len(),
+            self.changed.len(),
+            self.deleted.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn changes_between_snapshots_of_a_directory() {
+        let root = std::env::temp_dir().join(format!("analyzer-watch-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/kept.rs"), "").unwrap();
+        fs::write(root.join("src/changed.rs"), "").unwrap();
+        fs::write(root.join("src/deleted.rs"), "").unwrap();
+        let config = AnalyzerConfig::default();
+        let roots = [root.clone()];
+        let before = Snapshot::take(&roots, &config).unwrap();
+
+        fs::write(root.join("src/created.rs"), "").unwrap();
+        fs::remove_file(root.join("src/deleted.rs")).unwrap();
+        // Set the time explicitly, as the clock may not have advanced since the first write
+        let changed = File::options()
+            .write(true)
+            .open(root.join("src/changed.rs"))
+            .unwrap();
+        let modified = changed.metadata().unwrap().modified().unwrap();
+        changed
+            .set_modified(modified + Duration::from_secs(1))
+            .unwrap();
+        drop(changed);
+        let after = Snapshot::take(&roots, &config).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let changes = FileChanges::between(&before, &after);
+        assert_eq!(changes.created, [root.join("src/created.rs")]);
+        assert_eq!(changes.changed, [root.join("src/changed.rs")]);
+        assert_eq!(changes.deleted, [root.join("src/deleted.rs")]);
+        assert!(FileChanges::between(&after, &after).is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Poll `path` until its contents satisfy `done`, giving up after `timeout`.
+fn wait_for_output(path: &Path, timeout: Duration, done: impl Fn(&str) -> bool) -> Option<String> {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if let Ok(contents) = fs::read_to_string(path) {
+            if done(&contents) {
+                return Some(contents);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    None
+}
+
+#[test]
+fn watch_mode_rewrites_the_output_after_a_change() {
+    let has_server = Command::new("rust-analyzer")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !has_server {
+        eprintln!("Skipping, rust-analyzer is not installed");
+        return;
+    }
+
+    let root = std::env::temp_dir().join(format!("analyzer-watch-mode-{}", std::process::id()));
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"watched\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    fs::write(root.join("src/lib.rs"), "pub fn first() {}\n").unwrap();
+    let output = root.join("graph.json");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_analyzer"))
+        .args(["--watch", "-o"])
+        .arg(&output)
+        .current_dir(&root)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let initial = wait_for_output(&output, Duration::from_secs(120), |contents| {
+        contents.contains("\"first\"")
+    });
+    // Wait out the poll interval, so the next write counts as a change
+    std::thread::sleep(Duration::from_secs(1));
+    fs::write(
+        root.join("src/lib.rs"),
+        "pub fn first() {}\npub fn second() {}\n",
+    )
+    .unwrap();
+    let updated = wait_for_output(&output, Duration::from_secs(60), |contents| {
+        contents.contains("\"second\"")
+    });
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&root).unwrap();
+
+    assert!(initial.is_some(), "the initial graph was not written");
+    let updated = updated.expect("the graph was not rewritten after the change");
+    assert!(updated.contains("\"first\""));
+}
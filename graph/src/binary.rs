@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EdgeData, EdgeId, Graph, NodeContents, NodeData, NodeId, Severity};
+
+/// The binary form of a node. Metadata is stored as JSON, since bincode cannot
+/// encode arbitrary JSON values or skipped fields.
+#[derive(Serialize)]
+struct NodeRef<'a> {
+    id: NodeId,
+    contents: &'a NodeContents,
+    max_severity: Option<Severity>,
+    metadata: String,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    id: NodeId,
+    contents: NodeContents,
+    max_severity: Option<Severity>,
+    metadata: String,
+}
+
+#[derive(Serialize)]
+struct GraphRef<'a> {
+    nodes: Vec<NodeRef<'a>>,
+    edges: Vec<(EdgeId, &'a EdgeData)>,
+    path_root: Option<&'a Path>,
+}
+
+#[derive(Deserialize)]
+struct BinaryGraph {
+    nodes: Vec<Node>,
+    edges: Vec<(EdgeId, EdgeData)>,
+    path_root: Option<PathBuf>,
+}
+
+impl Graph {
+    /// Encode the graph in a compact binary format, meant for caching.
+    /// Use JSON to exchange graphs with other tools.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut node_ids: Vec<_> = self.nodes().collect();
+        node_ids.sort_unstable();
+        let nodes = node_ids
+            .into_iter()
+            .map(|id| {
+                let node = self.node(id).unwrap();
+                NodeRef {
+                    id,
+                    contents: &node.contents,
+                    max_severity: node.max_severity,
+                    metadata: serde_json::to_string(&node.metadata).unwrap(),
+                }
+            })
+            .collect();
+
+        let mut edge_ids: Vec<_> = self.edges().collect();
+        edge_ids.sort_unstable();
+        let edges = edge_ids
+            .into_iter()
+            .map(|id| (id, self.edge(id).unwrap()))
+            .collect();
+
+        let graph = GraphRef {
+            nodes,
+            edges,
+            path_root: self.path_root(),
+        };
+        bincode::serialize(&graph).unwrap()
+    }
+
+    /// Decode a graph encoded by [`Graph::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> bincode::Result<Graph> {
+        let BinaryGraph {
+            nodes,
+            edges,
+            path_root,
+        } = bincode::deserialize(bytes)?;
+        let nodes = nodes
+            .into_iter()
+            .map(|node| {
+                let metadata = serde_json::from_str(&node.metadata)
+                    .map_err(|error| bincode::ErrorKind::Custom(error.to_string()))?;
+                let mut data = NodeData::new(node.contents);
+                data.max_severity = node.max_severity;
+                data.metadata = metadata;
+                Ok((node.id, data))
+            })
+            .collect::<bincode::Result<_>>()?;
+        let mut graph = Graph::from_parts(nodes, edges.into_iter().collect());
+        graph.path_root = path_root;
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{Position, Range, Relation, SymbolKind};
+
+    fn sample_graph() -> Graph {
+        let mut graph = Graph::default();
+        let folder = graph.add_node(NodeData::new(NodeContents::Folder {
+            display_name: "src".to_owned(),
+            path: PathBuf::from("/project/src"),
+        }));
+        let file = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "lib.rs".to_owned(),
+            path: PathBuf::from("/project/src/lib.rs"),
+        }));
+        let range = Range {
+            start: Position {
+                line: 1,
+                character: 0,
+            },
+            end: Position {
+                line: 3,
+                character: 1,
+            },
+        };
+        let mut item = NodeData::new(NodeContents::Item {
+            display_name: "main".to_owned(),
+            moniker: Some("project::main".to_owned()),
+            range: Some(range),
+            selection_range: None,
+            doc: Some("The entry point.".to_owned()),
+            kind: Some(SymbolKind::Function),
+        });
+        item.max_severity = Some(Severity::Warning);
+        item.metadata
+            .insert("coverage".to_owned(), serde_json::json!(0.5));
+        let item = graph.add_node(item);
+        for (from, to) in [(folder, file), (file, item)] {
+            graph.add_edge(EdgeData {
+                from,
+                to,
+                relation: Relation::IsParentOf,
+            });
+        }
+        graph
+    }
+
+    /// The nodes and edges of a graph as JSON values, keyed by ID.
+    fn contents(graph: &Graph) -> (HashMap<NodeId, String>, HashMap<EdgeId, String>) {
+        let nodes = graph
+            .nodes()
+            .map(|id| (id, serde_json::to_string(graph.node(id).unwrap()).unwrap()))
+            .collect();
+        let edges = graph
+            .edges()
+            .map(|id| (id, serde_json::to_string(graph.edge(id).unwrap()).unwrap()))
+            .collect();
+        (nodes, edges)
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut graph = sample_graph();
+        graph.make_paths_relative(Path::new("/project"));
+        let decoded = Graph::from_bytes(&graph.to_bytes()).unwrap();
+        assert_eq!(contents(&decoded), contents(&graph));
+        assert_eq!(decoded.path_root(), Some(Path::new("/project")));
+    }
+
+    #[test]
+    fn bytes_are_smaller_than_json() {
+        let graph = sample_graph();
+        let json = serde_json::to_vec(&graph).unwrap();
+        assert!(graph.to_bytes().len() < json.len());
+    }
+}
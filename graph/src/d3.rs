@@ -0,0 +1,119 @@
+use serde_json::{json, Value};
+
+use crate::{Graph, NodeContents, Relation};
+
+impl Graph {
+    /// Export the graph as `{nodes: [...], links: [...]}` in the shape d3 expects.
+    pub fn to_d3_json(&self) -> Value {
+        let mut node_ids: Vec<_> = self.nodes().collect();
+        node_ids.sort_unstable();
+        let nodes: Vec<Value> = node_ids
+            .into_iter()
+            .map(|node_id| {
+                let node = self.node(node_id).unwrap();
+                match &node.contents {
+                    NodeContents::Workspace { display_name } => json!({
+                        "id": node_id,
+                        "type": "Workspace",
+                        "name": display_name,
+                    }),
+                    NodeContents::Folder { display_name, path } => json!({
+                        "id": node_id,
+                        "type": "Folder",
+                        "name": display_name,
+                        "path": path,
+                    }),
+                    NodeContents::File { display_name, path } => json!({
+                        "id": node_id,
+                        "type": "File",
+                        "name": display_name,
+                        "path": path,
+                    }),
+                    NodeContents::Crate {
+                        display_name,
+                        manifest_path,
+                    } => json!({
+                        "id": node_id,
+                        "type": "Crate",
+                        "name": display_name,
+                        "path": manifest_path,
+                    }),
+                    NodeContents::Item {
+                        display_name, kind, ..
+                    } => json!({
+                        "id": node_id,
+                        "type": "Item",
+                        "name": display_name,
+                        "kind": kind,
+                    }),
+                }
+            })
+            .collect();
+
+        let mut edge_ids: Vec<_> = self.edges().collect();
+        edge_ids.sort_unstable();
+        let links: Vec<Value> = edge_ids
+            .into_iter()
+            .map(|edge_id| {
+                let edge = self.edge(edge_id).unwrap();
+                let relation = match edge.relation {
+                    Relation::IsParentOf => "IsParentOf",
+                    Relation::DeclaresModule => "DeclaresModule",
+                };
+                json!({
+                    "source": edge.from,
+                    "target": edge.to,
+                    "relation": relation,
+                })
+            })
+            .collect();
+
+        json!({
+            "nodes": nodes,
+            "links": links,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{EdgeData, NodeData, SymbolKind};
+
+    #[test]
+    fn d3_json_lists_nodes_and_links() {
+        let mut graph = Graph::default();
+        let folder = graph.add_node(NodeData::new(NodeContents::Folder {
+            display_name: "src".to_owned(),
+            path: PathBuf::from("src"),
+        }));
+        let item = graph.add_node(NodeData::new(NodeContents::Item {
+            display_name: "main".to_owned(),
+            moniker: None,
+            range: None,
+            selection_range: None,
+            doc: None,
+            kind: Some(SymbolKind::Function),
+        }));
+        graph.add_edge(EdgeData {
+            from: folder,
+            to: item,
+            relation: Relation::IsParentOf,
+        });
+
+        assert_eq!(
+            graph.to_d3_json(),
+            json!({
+                "nodes": [
+                    {"id": folder, "type": "Folder", "name": "src", "path": "src"},
+                    {"id": item, "type": "Item", "name": "main", "kind": "Function"},
+                ],
+                "links": [
+                    {"source": folder, "target": item, "relation": "IsParentOf"},
+                ],
+            })
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::Write;
+
+use crate::{Graph, NodeContents, Relation, SymbolKind};
+
+impl Graph {
+    /// Export the graph in the Graphviz DOT format, styling items by their symbol kind.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        out.push_str("digraph {\n");
+
+        let mut nodes: Vec<_> = self.nodes().collect();
+        nodes.sort_unstable();
+        for node_id in nodes {
+            let contents = &self.node(node_id).unwrap().contents;
+            let (shape, color) = node_style(contents);
+            writeln!(
+                out,
+                "    n{} [label=\"{}\", shape={}, style=filled, fillcolor=\"{}\"];",
+                node_id,
+                escape_dot(contents.display_name()),
+                shape,
+                color
+            )
+            .unwrap();
+        }
+
+        let mut edges: Vec<_> = self.edges().collect();
+        edges.sort_unstable();
+        for edge_id in edges {
+            let edge = self.edge(edge_id).unwrap();
+            let style = match edge.relation {
+                Relation::IsParentOf => "solid",
+                Relation::DeclaresModule => "dashed",
+            };
+            writeln!(out, "    n{} -> n{} [style={}];", edge.from, edge.to, style).unwrap();
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// The Graphviz shape and fill color of a node.
+fn node_style(contents: &NodeContents) -> (&'static str, &'static str) {
+    match contents {
+        NodeContents::Workspace { .. } => ("house", "#d3d3d3"),
+        NodeContents::Folder { .. } => ("folder", "#f5deb3"),
+        NodeContents::File { .. } => ("note", "#ffffff"),
+        NodeContents::Crate { .. } => ("box3d", "#ffa07a"),
+        NodeContents::Item { kind, .. } => match kind {
+            Some(SymbolKind::Function | SymbolKind::Method | SymbolKind::Constructor) => {
+                ("ellipse", "#add8e6")
+            }
+            Some(
+                SymbolKind::Struct
+                | SymbolKind::Class
+                | SymbolKind::Enum
+                | SymbolKind::Interface
+                | SymbolKind::TypeParameter,
+            ) => ("box", "#90ee90"),
+            Some(SymbolKind::Module | SymbolKind::Namespace | SymbolKind::Package) => {
+                ("tab", "#d3d3d3")
+            }
+            Some(
+                SymbolKind::Field
+                | SymbolKind::Property
+                | SymbolKind::EnumMember
+                | SymbolKind::Constant
+                | SymbolKind::Variable,
+            ) => ("plain", "#ffffff"),
+            _ => ("box", "#ffffff"),
+        },
+    }
+}
+
+/// Escape a string for use in a quoted DOT identifier.
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{EdgeData, NodeData};
+
+    #[test]
+    fn dot_styles_items_by_kind() {
+        let mut graph = Graph::default();
+        let file = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "main.rs".to_owned(),
+            path: PathBuf::from("main.rs"),
+        }));
+        let item = graph.add_node(NodeData::new(NodeContents::Item {
+            display_name: "main".to_owned(),
+            moniker: None,
+            range: None,
+            selection_range: None,
+            doc: None,
+            kind: Some(SymbolKind::Function),
+        }));
+        graph.add_edge(EdgeData {
+            from: file,
+            to: item,
+            relation: Relation::IsParentOf,
+        });
+
+        assert_eq!(
+            graph.to_dot(),
+            "digraph {\n\
+             \x20   n0 [label=\"main.rs\", shape=note, style=filled, fillcolor=\"#ffffff\"];\n\
+             \x20   n1 [label=\"main\", shape=ellipse, style=filled, fillcolor=\"#add8e6\"];\n\
+             \x20   n0 -> n1 [style=solid];\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn escape_dot_escapes_quotes_and_backslashes() {
+        assert_eq!(escape_dot(r#"say "hi" \ bye"#), r#"say \"hi\" \\ bye"#);
+    }
+}
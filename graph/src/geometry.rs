@@ -0,0 +1,201 @@
+/// A position in the layout.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+}
+
+/// The width and height of a node.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Size {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+}
+
+/// An axis-aligned rectangle from the top left corner `(x0, y0)` to the bottom
+/// right corner `(x1, y1)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rect {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+}
+
+impl Rect {
+    pub fn from_origin_size(origin: Point, size: Size) -> Self {
+        Self {
+            x0: origin.x,
+            y0: origin.y,
+            x1: origin.x + size.width,
+            y1: origin.y + size.height,
+        }
+    }
+
+    /// The top left corner.
+    pub fn origin(&self) -> Point {
+        Point::new(self.x0, self.y0)
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width(), self.height())
+    }
+
+    pub fn width(&self) -> f64 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> f64 {
+        self.y1 - self.y0
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new((self.x0 + self.x1) / 2.0, (self.y0 + self.y1) / 2.0)
+    }
+}
+
+/// A straight line from `p0` to `p1`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Line {
+    pub p0: Point,
+    pub p1: Point,
+}
+
+impl Line {
+    pub fn new(p0: Point, p1: Point) -> Self {
+        Self { p0, p1 }
+    }
+
+    pub fn length(&self) -> f64 {
+        (self.p1.x - self.p0.x).hypot(self.p1.y - self.p0.y)
+    }
+}
+
+/// A section of an ellipse, with angles in radians.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EllipticalArc {
+    pub center: Point,
+    pub radius_x: f64,
+    pub radius_y: f64,
+    pub start_angle: f64,
+    pub sweep_angle: f64,
+    /// Rotation of the ellipse's x axis.
+    pub x_rotation: f64,
+}
+
+impl From<kurbo::Point> for Point {
+    fn from(point: kurbo::Point) -> Self {
+        Self::new(point.x, point.y)
+    }
+}
+
+impl From<Point> for kurbo::Point {
+    fn from(point: Point) -> Self {
+        kurbo::Point::new(point.x, point.y)
+    }
+}
+
+impl From<kurbo::Size> for Size {
+    fn from(size: kurbo::Size) -> Self {
+        Self::new(size.width, size.height)
+    }
+}
+
+impl From<Size> for kurbo::Size {
+    fn from(size: Size) -> Self {
+        kurbo::Size::new(size.width, size.height)
+    }
+}
+
+impl From<kurbo::Rect> for Rect {
+    fn from(rect: kurbo::Rect) -> Self {
+        Self {
+            x0: rect.x0,
+            y0: rect.y0,
+            x1: rect.x1,
+            y1: rect.y1,
+        }
+    }
+}
+
+impl From<Rect> for kurbo::Rect {
+    fn from(rect: Rect) -> Self {
+        kurbo::Rect::new(rect.x0, rect.y0, rect.x1, rect.y1)
+    }
+}
+
+impl From<kurbo::Line> for Line {
+    fn from(line: kurbo::Line) -> Self {
+        Self::new(line.p0.into(), line.p1.into())
+    }
+}
+
+impl From<Line> for kurbo::Line {
+    fn from(line: Line) -> Self {
+        kurbo::Line::new(kurbo::Point::from(line.p0), kurbo::Point::from(line.p1))
+    }
+}
+
+impl From<kurbo::Arc> for EllipticalArc {
+    fn from(arc: kurbo::Arc) -> Self {
+        Self {
+            center: arc.center.into(),
+            radius_x: arc.radii.x,
+            radius_y: arc.radii.y,
+            start_angle: arc.start_angle,
+            sweep_angle: arc.sweep_angle,
+            x_rotation: arc.x_rotation,
+        }
+    }
+}
+
+impl From<EllipticalArc> for kurbo::Arc {
+    fn from(arc: EllipticalArc) -> Self {
+        kurbo::Arc {
+            center: arc.center.into(),
+            radii: kurbo::Vec2::new(arc.radius_x, arc.radius_y),
+            start_angle: arc.start_angle,
+            sweep_angle: arc.sweep_angle,
+            x_rotation: arc.x_rotation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_round_trip_through_kurbo() {
+        let rect = Rect::from_origin_size(Point::new(1.0, 2.0), Size::new(30.0, 10.0));
+        assert_eq!(Rect::from(kurbo::Rect::from(rect)), rect);
+        assert_eq!(rect.center(), Point::new(16.0, 7.0));
+        assert_eq!(rect.size(), Size::new(30.0, 10.0));
+
+        let line = Line::new(Point::new(0.0, 0.0), Point::new(3.0, 4.0));
+        assert_eq!(Line::from(kurbo::Line::from(line)), line);
+        assert_eq!(line.length(), 5.0);
+
+        let arc = EllipticalArc {
+            center: Point::new(5.0, 5.0),
+            radius_x: 2.0,
+            radius_y: 1.0,
+            start_angle: 0.5,
+            sweep_angle: 3.0,
+            x_rotation: 0.0,
+        };
+        assert_eq!(EllipticalArc::from(kurbo::Arc::from(arc)), arc);
+    }
+}
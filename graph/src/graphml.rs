@@ -0,0 +1,161 @@
+use std::fmt::Write;
+
+use crate::{Graph, NodeContents, Relation};
+
+impl Graph {
+    /// Export the graph in the GraphML format, e.g. for yEd or Gephi.
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n");
+        out.push_str(
+            "  <key id=\"variant\" for=\"node\" attr.name=\"variant\" attr.type=\"string\"/>\n",
+        );
+        out.push_str("  <key id=\"path\" for=\"node\" attr.name=\"path\" attr.type=\"string\"/>\n");
+        out.push_str(
+            "  <key id=\"moniker\" for=\"node\" attr.name=\"moniker\" attr.type=\"string\"/>\n",
+        );
+        out.push_str("  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n");
+        out.push_str(
+            "  <key id=\"relation\" for=\"edge\" attr.name=\"relation\" attr.type=\"string\"/>\n",
+        );
+        out.push_str("  <graph id=\"G\" edgedefault=\"directed\">\n");
+
+        let mut nodes: Vec<_> = self.nodes().collect();
+        nodes.sort_unstable();
+        for node_id in nodes {
+            let node = self.node(node_id).unwrap();
+            let (variant, display_name, path, moniker, kind) = match &node.contents {
+                NodeContents::Workspace { display_name } => {
+                    ("Workspace", display_name, None, None, None)
+                }
+                NodeContents::Folder { display_name, path } => {
+                    ("Folder", display_name, Some(path), None, None)
+                }
+                NodeContents::File { display_name, path } => {
+                    ("File", display_name, Some(path), None, None)
+                }
+                NodeContents::Crate {
+                    display_name,
+                    manifest_path,
+                } => ("Crate", display_name, Some(manifest_path), None, None),
+                NodeContents::Item {
+                    display_name,
+                    moniker,
+                    kind,
+                    ..
+                } => ("Item", display_name, None, moniker.as_ref(), *kind),
+            };
+            writeln!(out, "    <node id=\"n{}\">", node_id).unwrap();
+            write_data(&mut out, "name", display_name);
+            write_data(&mut out, "variant", variant);
+            if let Some(path) = path {
+                write_data(&mut out, "path", &path.to_string_lossy());
+            }
+            if let Some(moniker) = moniker {
+                write_data(&mut out, "moniker", moniker);
+            }
+            if let Some(kind) = kind {
+                write_data(&mut out, "kind", &format!("{:?}", kind));
+            }
+            out.push_str("    </node>\n");
+        }
+
+        let mut edges: Vec<_> = self.edges().collect();
+        edges.sort_unstable();
+        for edge_id in edges {
+            let edge = self.edge(edge_id).unwrap();
+            writeln!(
+                out,
+                "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\">",
+                edge_id, edge.from, edge.to
+            )
+            .unwrap();
+            let relation = match edge.relation {
+                Relation::IsParentOf => "IsParentOf",
+                Relation::DeclaresModule => "DeclaresModule",
+            };
+            write_data(&mut out, "relation", relation);
+            out.push_str("    </edge>\n");
+        }
+
+        out.push_str("  </graph>\n");
+        out.push_str("</graphml>\n");
+        out
+    }
+}
+
+fn write_data(out: &mut String, key: &str, value: &str) {
+    writeln!(
+        out,
+        "      <data key=\"{}\">{}</data>",
+        key,
+        escape_xml(value)
+    )
+    .unwrap();
+}
+
+/// Escape XML special characters in text and attribute values.
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{EdgeData, NodeData};
+
+    #[test]
+    fn escape_xml_escapes_special_characters() {
+        assert_eq!(
+            escape_xml("Vec<T> & \"it's\""),
+            "Vec&lt;T&gt; &amp; &quot;it&apos;s&quot;"
+        );
+    }
+
+    #[test]
+    fn graphml_contains_nodes_and_edges() {
+        let mut graph = Graph::default();
+        let file = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "lib.rs".to_owned(),
+            path: PathBuf::from("src/lib.rs"),
+        }));
+        let item = graph.add_node(NodeData::new(NodeContents::Item {
+            display_name: "Option<T>".to_owned(),
+            moniker: None,
+            range: None,
+            selection_range: None,
+            doc: None,
+            kind: None,
+        }));
+        graph.add_edge(EdgeData {
+            from: file,
+            to: item,
+            relation: Relation::IsParentOf,
+        });
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains(&format!("<node id=\"n{file}\">")));
+        assert!(graphml.contains("<data key=\"path\">src/lib.rs</data>"));
+        assert!(graphml.contains("<data key=\"name\">Option&lt;T&gt;</data>"));
+        assert!(graphml.contains(&format!(
+            "<edge id=\"e0\" source=\"n{file}\" target=\"n{item}\">"
+        )));
+        assert!(graphml.contains("<data key=\"relation\">IsParentOf</data>"));
+        assert!(graphml.ends_with("</graphml>\n"));
+    }
+}
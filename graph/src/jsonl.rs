@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EdgeData, EdgeId, Graph, NodeData, NodeId};
+
+/// A single line of the JSON lines format.
+#[derive(Serialize)]
+enum RecordRef<'a> {
+    Node { id: NodeId, data: &'a NodeData },
+    Edge { id: EdgeId, data: &'a EdgeData },
+}
+
+#[derive(Deserialize)]
+enum Record {
+    Node { id: NodeId, data: NodeData },
+    Edge { id: EdgeId, data: EdgeData },
+}
+
+impl Graph {
+    /// Stream the graph as newline-delimited JSON, one record per node and per edge.
+    pub fn write_jsonl<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut node_ids: Vec<_> = self.nodes().collect();
+        node_ids.sort_unstable();
+        for id in node_ids {
+            let data = self.node(id).unwrap();
+            serde_json::to_writer(&mut writer, &RecordRef::Node { id, data })?;
+            writer.write_all(b"\n")?;
+        }
+
+        let mut edge_ids: Vec<_> = self.edges().collect();
+        edge_ids.sort_unstable();
+        for id in edge_ids {
+            let data = self.edge(id).unwrap();
+            serde_json::to_writer(&mut writer, &RecordRef::Edge { id, data })?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()
+    }
+
+    /// Load a graph written by [`Graph::write_jsonl`].
+    pub fn read_jsonl<R: BufRead>(reader: R) -> io::Result<Graph> {
+        let mut nodes = HashMap::new();
+        let mut edges = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line)? {
+                Record::Node { id, data } => {
+                    nodes.insert(id, data);
+                }
+                Record::Edge { id, data } => {
+                    edges.insert(id, data);
+                }
+            }
+        }
+        Ok(Graph::from_parts(nodes, edges))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{NodeContents, Relation};
+
+    #[test]
+    fn jsonl_round_trip() {
+        let mut graph = Graph::default();
+        let folder = graph.add_node(NodeData::new(NodeContents::Folder {
+            display_name: "src".to_owned(),
+            path: PathBuf::from("src"),
+        }));
+        let file = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "lib.rs".to_owned(),
+            path: PathBuf::from("src/lib.rs"),
+        }));
+        let removed = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "old.rs".to_owned(),
+            path: PathBuf::from("src/old.rs"),
+        }));
+        graph.add_edge(EdgeData {
+            from: folder,
+            to: file,
+            relation: Relation::IsParentOf,
+        });
+        // IDs are kept, even with gaps left by removed nodes
+        graph.remove_node(removed);
+        let item = graph.add_node(NodeData::new(NodeContents::Item {
+            display_name: "main".to_owned(),
+            moniker: None,
+            range: None,
+            selection_range: None,
+            doc: None,
+            kind: None,
+        }));
+        graph.add_edge(EdgeData {
+            from: file,
+            to: item,
+            relation: Relation::IsParentOf,
+        });
+
+        let mut buffer = Vec::new();
+        graph.write_jsonl(&mut buffer).unwrap();
+        assert_eq!(buffer.iter().filter(|&&byte| byte == b'\n').count(), 5);
+        let mut decoded = Graph::read_jsonl(buffer.as_slice()).unwrap();
+
+        assert_eq!(decoded.node_count(), 3);
+        assert!(decoded.node(removed).is_none());
+        for id in graph.nodes() {
+            let expected = serde_json::to_value(graph.node(id).unwrap()).unwrap();
+            let actual = serde_json::to_value(decoded.node(id).unwrap()).unwrap();
+            assert_eq!(actual, expected);
+        }
+        assert_eq!(decoded.node_children(folder), Some(vec![file]));
+        assert_eq!(decoded.node_children(file), Some(vec![item]));
+        // New nodes do not reuse the IDs of the loaded ones
+        let added = decoded.add_node(NodeData::new(NodeContents::Folder {
+            display_name: "tests".to_owned(),
+            path: PathBuf::from("tests"),
+        }));
+        assert!(added > item);
+    }
+}
@@ -1,49 +1,507 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::{EdgeId, Graph, NodeId};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{EdgeId, EllipticalArc, Graph, Line, NodeId, Point, Rect, Relation};
 
 const IDEAL_SPRING_LENGTH: f64 = 50.0;
 
+const NODE_WIDTH: f64 = 64.0;
+const NODE_HEIGHT: f64 = 100.0;
+const NODE_PADDING: f64 = 8.0;
+/// Approximate width of a character, used when no text measurement is configured.
+const CHAR_WIDTH: f64 = 8.0;
+const GRID_SPACING: f64 = 150.0;
+const GRID_GAP: f64 = 20.0;
+
+const GRAVITY: f64 = 0.01;
+
+const INITIAL_TEMPERATURE: f64 = 1.0;
+const MAX_DISPLACEMENT: f64 = 100.0;
+
+/// Iterations of the force simulation when refining saved positions.
+const REFINE_ITERATIONS: usize = 1000;
+
+/// How often the force simulation checks whether `max_duration` has passed.
+const DEADLINE_CHECK_INTERVAL: usize = 100;
+
+const TRACE_INTERVAL: usize = 100;
+const MAX_TRACE_FRAMES: usize = 100;
+
+const PARALLEL_EDGE_SPACING: f64 = 10.0;
+const SELF_LOOP_RADIUS: f64 = 16.0;
+
+/// How the step size of the force simulation decreases over the iterations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoolingSchedule {
+    /// Decrease linearly to zero at `max_iterations`.
+    Linear,
+    /// Multiply the temperature by `rate` (between 0 and 1) every iteration.
+    Exponential { rate: f64 },
+    /// `alpha / (1 + beta * t * step / max_iterations)^gamma` for initial temperature `t`.
+    InverseTime { alpha: f64, beta: f64, gamma: f64 },
+}
+
+impl CoolingSchedule {
+    pub fn factor(&self, initial_temperature: f64, step: usize, max_iterations: usize) -> f64 {
+        let progress = step as f64 / max_iterations as f64;
+        match *self {
+            CoolingSchedule::Linear => initial_temperature * (1.0 - progress).max(0.0),
+            CoolingSchedule::Exponential { rate } => initial_temperature * rate.powi(step as i32),
+            CoolingSchedule::InverseTime { alpha, beta, gamma } => {
+                initial_temperature * alpha
+                    / (1.0 + beta * initial_temperature * progress).powf(gamma)
+            }
+        }
+    }
+}
+
+impl Default for CoolingSchedule {
+    fn default() -> Self {
+        CoolingSchedule::InverseTime {
+            alpha: 1.0,
+            beta: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+/// Measures the rendered width of a text.
+pub type MeasureText = Arc<dyn Fn(&str) -> f64 + Send + Sync>;
+
+#[derive(Clone)]
+pub struct LayoutConfig {
+    /// Ideal length used for repulsion and for relations without an explicit length.
+    pub ideal_spring_length: f64,
+    /// Ideal edge length per relation, overriding `ideal_spring_length`.
+    pub relation_spring_lengths: HashMap<Relation, f64>,
+    /// Scales the force pushing all nodes apart.
+    pub repulsion_strength: f64,
+    /// Scales the force pulling the nodes of an edge together.
+    pub attraction_strength: f64,
+    /// The relations whose edges attract their nodes. `None` means all relations.
+    /// Edges of other relations are still laid out but do not affect node positions.
+    pub relations: Option<HashSet<Relation>>,
+    /// Graphs with more nodes than this are laid out as a grid instead.
+    pub grid_fallback_threshold: Option<usize>,
+    /// Measures display names to size nodes. Falls back to counting characters.
+    pub measure_text: Option<MeasureText>,
+    /// How the step size decreases over the iterations.
+    pub cooling_schedule: CoolingSchedule,
+    /// The step size of the first iteration, before cooling.
+    pub initial_temperature: f64,
+    /// The maximum distance a node moves in a single iteration.
+    pub max_displacement: f64,
+    /// Stop the force simulation after this long, keeping the positions reached so far.
+    pub max_duration: Option<Duration>,
+    /// Strength of the force pulling every node towards the center.
+    pub gravity: f64,
+    /// Scales the repulsion between nodes that are neither siblings nor parent and child.
+    /// Values below 1 let separate subtrees sit closer and form tighter clusters.
+    pub unrelated_repulsion: f64,
+    /// The point nodes are pulled towards. Defaults to the centroid of all nodes.
+    pub center: Option<Point>,
+    /// Record a trace frame every this many iterations.
+    pub trace_interval: usize,
+    /// The maximum number of recorded trace frames.
+    pub max_trace_frames: usize,
+    /// Scatter the nodes randomly before the force simulation, drawing from a generator
+    /// seeded with this value. Without a seed, the nodes start on a diagonal.
+    pub seed: Option<u64>,
+}
+
+impl LayoutConfig {
+    /// The ideal length of an edge with the given relation.
+    pub fn ideal_length(&self, relation: Relation) -> f64 {
+        self.relation_spring_lengths
+            .get(&relation)
+            .copied()
+            .unwrap_or(self.ideal_spring_length)
+    }
+
+    /// The size of the rectangle for a node.
+    fn node_size(&self, graph: &Graph, node_id: NodeId) -> kurbo::Size {
+        let display_name = graph.node(node_id).unwrap().contents.display_name();
+        let text_width = match &self.measure_text {
+            Some(measure_text) => measure_text(display_name),
+            None => display_name.chars().count() as f64 * CHAR_WIDTH,
+        };
+        kurbo::Size::new(
+            (text_width + 2.0 * NODE_PADDING).max(NODE_WIDTH),
+            NODE_HEIGHT,
+        )
+    }
+}
+
+impl fmt::Debug for LayoutConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LayoutConfig")
+            .field("ideal_spring_length", &self.ideal_spring_length)
+            .field("relation_spring_lengths", &self.relation_spring_lengths)
+            .field("repulsion_strength", &self.repulsion_strength)
+            .field("attraction_strength", &self.attraction_strength)
+            .field("relations", &self.relations)
+            .field("grid_fallback_threshold", &self.grid_fallback_threshold)
+            .field("measure_text", &self.measure_text.as_ref().map(|_| ".."))
+            .field("cooling_schedule", &self.cooling_schedule)
+            .field("initial_temperature", &self.initial_temperature)
+            .field("max_displacement", &self.max_displacement)
+            .field("max_duration", &self.max_duration)
+            .field("gravity", &self.gravity)
+            .field("unrelated_repulsion", &self.unrelated_repulsion)
+            .field("center", &self.center)
+            .field("trace_interval", &self.trace_interval)
+            .field("max_trace_frames", &self.max_trace_frames)
+            .field("seed", &self.seed)
+            .finish()
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            ideal_spring_length: IDEAL_SPRING_LENGTH,
+            relation_spring_lengths: HashMap::new(),
+            repulsion_strength: 1.0,
+            attraction_strength: 1.0,
+            relations: None,
+            grid_fallback_threshold: None,
+            measure_text: None,
+            cooling_schedule: CoolingSchedule::default(),
+            initial_temperature: INITIAL_TEMPERATURE,
+            max_displacement: MAX_DISPLACEMENT,
+            max_duration: None,
+            gravity: GRAVITY,
+            unrelated_repulsion: 1.0,
+            center: None,
+            trace_interval: TRACE_INTERVAL,
+            max_trace_frames: MAX_TRACE_FRAMES,
+            seed: None,
+        }
+    }
+}
+
+/// Node positions at an intermediate step of the force simulation.
+#[derive(Debug, Clone)]
+pub struct LayoutFrame {
+    pub step: usize,
+    pub positions: HashMap<NodeId, Point>,
+}
+
+impl LayoutFrame {
+    fn capture(step: usize, layout: &Layout) -> Self {
+        Self {
+            step,
+            positions: layout
+                .rects
+                .iter()
+                .map(|(&node_id, rect)| (node_id, rect.origin().into()))
+                .collect(),
+        }
+    }
+}
+
+/// Frames recorded during the force simulation.
+struct Trace {
+    frames: Vec<LayoutFrame>,
+    interval: usize,
+    max_frames: usize,
+}
+
+impl Trace {
+    fn record(&mut self, step: usize, layout: &Layout) {
+        if !step.is_multiple_of(self.interval) {
+            return;
+        }
+        if self.frames.len() == self.max_frames {
+            // Halve the resolution to stay within the cap
+            let mut i = 0;
+            self.frames.retain(|_| {
+                i += 1;
+                i % 2 == 1
+            });
+            self.interval *= 2;
+            if !step.is_multiple_of(self.interval) {
+                return;
+            }
+        }
+        self.frames.push(LayoutFrame::capture(step, layout));
+    }
+}
+
+/// Measures for comparing layouts, see [`Layout::quality`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutQuality {
+    /// Pairs of edges without a common node whose lines cross.
+    pub edge_crossings: usize,
+    /// Pairs of nodes whose rectangles overlap.
+    pub node_overlaps: usize,
+    /// The mean length of all edge lines, or 0 if there are none.
+    pub average_edge_length: f64,
+}
+
 #[derive(Debug)]
 pub struct Layout {
-    pub rects: HashMap<NodeId, kurbo::Rect>,
-    pub lines: HashMap<EdgeId, kurbo::Line>,
+    rects: HashMap<NodeId, kurbo::Rect>,
+    lines: HashMap<EdgeId, kurbo::Line>,
+    /// Self-loops, drawn as arcs around the top right corner of their node.
+    arcs: HashMap<EdgeId, kurbo::Arc>,
 }
 
 impl Layout {
+    /// The rectangle of a node.
+    pub fn rect(&self, node_id: NodeId) -> Option<Rect> {
+        self.rects.get(&node_id).map(|&rect| rect.into())
+    }
+
+    pub fn rects(&self) -> impl Iterator<Item = (NodeId, Rect)> + '_ {
+        self.rects
+            .iter()
+            .map(|(&node_id, &rect)| (node_id, rect.into()))
+    }
+
+    /// The line of an edge between two different nodes.
+    pub fn line(&self, edge_id: EdgeId) -> Option<Line> {
+        self.lines.get(&edge_id).map(|&line| line.into())
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = (EdgeId, Line)> + '_ {
+        self.lines
+            .iter()
+            .map(|(&edge_id, &line)| (edge_id, line.into()))
+    }
+
+    /// The arc of a self-loop, drawn around the top right corner of its node.
+    pub fn arc(&self, edge_id: EdgeId) -> Option<EllipticalArc> {
+        self.arcs.get(&edge_id).map(|&arc| arc.into())
+    }
+
+    pub fn arcs(&self) -> impl Iterator<Item = (EdgeId, EllipticalArc)> + '_ {
+        self.arcs
+            .iter()
+            .map(|(&edge_id, &arc)| (edge_id, arc.into()))
+    }
+
     pub fn compute(graph: &Graph) -> Self {
-        let mut layout = initial_layout(graph);
+        Self::compute_with_config(graph, &LayoutConfig::default())
+    }
 
-        apply_forces(graph, &mut layout, 0.1, 50000);
+    pub fn compute_with_config(graph: &Graph, config: &LayoutConfig) -> Self {
+        if let Some(threshold) = config.grid_fallback_threshold {
+            if graph.node_count() > threshold {
+                let cols = (graph.node_count() as f64).sqrt().ceil() as usize;
+                return Self::grid_with_config(graph, cols, config);
+            }
+        }
+
+        let mut layout = initial_layout(graph, config);
+
+        apply_forces(graph, &mut layout, config, 0.1, 50000, None);
+
+        layout_edges(graph, &mut layout);
+
+        layout
+    }
+
+    /// Compute the layout, recording node positions every `config.trace_interval`
+    /// iterations. The last frame always holds the final positions.
+    pub fn compute_with_trace(graph: &Graph, config: &LayoutConfig) -> (Self, Vec<LayoutFrame>) {
+        let mut layout = initial_layout(graph, config);
+        let mut trace = Trace {
+            frames: vec![LayoutFrame::capture(0, &layout)],
+            interval: config.trace_interval.max(1),
+            max_frames: config.max_trace_frames.max(2),
+        };
+
+        let steps = apply_forces(graph, &mut layout, config, 0.1, 50000, Some(&mut trace));
+
+        layout_edges(graph, &mut layout);
+
+        let mut frames = trace.frames;
+        if frames.len() == trace.max_frames {
+            frames.pop();
+        }
+        frames.push(LayoutFrame::capture(steps, &layout));
+
+        (layout, frames)
+    }
+
+    /// Start from previously saved node origins and refine them with a short force simulation.
+    /// Nodes without a saved position are placed next to their parent, or along a diagonal.
+    pub fn from_positions(
+        graph: &Graph,
+        positions: &HashMap<NodeId, (f64, f64)>,
+        config: &LayoutConfig,
+    ) -> Self {
+        let mut layout = Layout {
+            rects: HashMap::new(),
+            lines: HashMap::new(),
+            arcs: HashMap::new(),
+        };
+
+        let mut node_ids: Vec<_> = graph.nodes().collect();
+        node_ids.sort_unstable();
+        let mut next_diagonal = 0.0;
+        for node_id in node_ids {
+            let parent_position = graph
+                .node_parent(node_id)
+                .and_then(|parent| positions.get(&parent));
+            let origin = match (positions.get(&node_id), parent_position) {
+                (Some(&(x, y)), _) => kurbo::Point::new(x, y),
+                (None, Some(&(x, y))) => kurbo::Point::new(x + GRID_SPACING, y + GRID_SPACING),
+                (None, None) => {
+                    next_diagonal += GRID_SPACING;
+                    kurbo::Point::new(next_diagonal, next_diagonal)
+                }
+            };
+            layout.rects.insert(
+                node_id,
+                kurbo::Rect::from_origin_size(origin, config.node_size(graph, node_id)),
+            );
+        }
+
+        apply_forces(graph, &mut layout, config, 0.1, REFINE_ITERATIONS, None);
+
+        layout_edges(graph, &mut layout);
+
+        layout
+    }
+
+    /// Arrange the nodes in a grid with `cols` columns, ordered by node ID.
+    pub fn grid(graph: &Graph, cols: usize) -> Self {
+        Self::grid_with_config(graph, cols, &LayoutConfig::default())
+    }
+
+    pub fn grid_with_config(graph: &Graph, cols: usize, config: &LayoutConfig) -> Self {
+        let cols = cols.max(1);
+        let mut layout = Layout {
+            rects: HashMap::new(),
+            lines: HashMap::new(),
+            arcs: HashMap::new(),
+        };
+
+        let mut node_ids: Vec<_> = graph.nodes().collect();
+        node_ids.sort_unstable();
+        let sizes: Vec<_> = node_ids
+            .iter()
+            .map(|&node_id| config.node_size(graph, node_id))
+            .collect();
+        // Widen the columns so that wide nodes do not overlap
+        let max_width = sizes.iter().map(|size| size.width).fold(0.0, f64::max);
+        let column_spacing = GRID_SPACING.max(max_width + GRID_GAP);
+        for (i, (node_id, size)) in node_ids.into_iter().zip(sizes).enumerate() {
+            let x = (i % cols) as f64 * column_spacing;
+            let y = (i / cols) as f64 * GRID_SPACING;
+            layout
+                .rects
+                .insert(node_id, kurbo::Rect::from_origin_size((x, y), size));
+        }
 
         layout_edges(graph, &mut layout);
 
         layout
     }
+
+    /// Count edge crossings and node overlaps. Self-loops are not considered.
+    pub fn quality(&self, graph: &Graph) -> LayoutQuality {
+        let edges: Vec<_> = self
+            .lines
+            .iter()
+            .filter_map(|(&edge_id, &line)| Some((graph.edge(edge_id)?, line)))
+            .collect();
+        let mut edge_crossings = 0;
+        for (i, (a, a_line)) in edges.iter().enumerate() {
+            for (b, b_line) in &edges[i + 1..] {
+                // Edges meeting at a common node always touch there
+                let shares_node = [a.from, a.to].iter().any(|&n| n == b.from || n == b.to);
+                if !shares_node
+                    && !kurbo::PathSeg::Line(*a_line)
+                        .intersect_line(*b_line)
+                        .is_empty()
+                {
+                    edge_crossings += 1;
+                }
+            }
+        }
+
+        let rects: Vec<_> = self.rects.values().collect();
+        let mut node_overlaps = 0;
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                if a.intersect(**b).area() > 0.0 {
+                    node_overlaps += 1;
+                }
+            }
+        }
+
+        let average_edge_length = if self.lines.is_empty() {
+            0.0
+        } else {
+            self.lines.values().map(|line| line.length()).sum::<f64>() / self.lines.len() as f64
+        };
+
+        LayoutQuality {
+            edge_crossings,
+            node_overlaps,
+            average_edge_length,
+        }
+    }
 }
 
-fn apply_forces(graph: &Graph, layout: &mut Layout, threshold: f64, max_iterations: usize) {
-    let initial_temperature: f64 = 1.0;
+fn apply_forces(
+    graph: &Graph,
+    layout: &mut Layout,
+    config: &LayoutConfig,
+    threshold: f64,
+    max_iterations: usize,
+    mut trace: Option<&mut Trace>,
+) -> usize {
+    let initial_temperature = config.initial_temperature;
     let mut step = 1;
     let mut forces = HashMap::new();
+    // A duration too long to represent as an instant never runs out
+    let deadline = config
+        .max_duration
+        .and_then(|duration| Instant::now().checked_add(duration));
+    // Sum forces in a fixed order, so that the same input always gives the same layout.
+    // Nodes without a rectangle, e.g. added after the layout, stay unplaced.
+    let mut node_ids: Vec<_> = graph
+        .nodes()
+        .filter(|node_id| layout.rects.contains_key(node_id))
+        .collect();
+    node_ids.sort_unstable();
 
     while step < max_iterations {
         let mut max_force = kurbo::Vec2::new(0.0, 0.0);
+        let center = config
+            .center
+            .map(kurbo::Point::from)
+            .unwrap_or_else(|| centroid(layout, &node_ids));
 
-        for node_id in graph.nodes() {
-            let force = compute_force(graph, layout, node_id);
-            let delta = cooling_factor(initial_temperature, step, max_iterations);
-            forces.insert(node_id, delta * force);
+        for &node_id in &node_ids {
+            let force = compute_force(graph, layout, config, center, &node_ids, node_id);
+            let delta = config
+                .cooling_schedule
+                .factor(initial_temperature, step, max_iterations);
+            let displacement = clamp_displacement(delta * force, config.max_displacement);
+            forces.insert(node_id, displacement);
             if force.length() > max_force.length() {
                 max_force = force;
             }
         }
 
-        for node_id in graph.nodes() {
-            let rect = layout.rects[&node_id];
-            let new_rect =
-                kurbo::Rect::from_origin_size(rect.origin() + forces[&node_id], rect.size());
-            layout.rects.insert(node_id, new_rect);
+        for (node_id, &force) in &forces {
+            if let Some(rect) = layout.rects.get_mut(node_id) {
+                *rect = kurbo::Rect::from_origin_size(rect.origin() + force, rect.size());
+            }
+        }
+
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.record(step, layout);
         }
 
         if max_force.length() < threshold {
@@ -51,49 +509,119 @@ fn apply_forces(graph: &Graph, layout: &mut Layout, threshold: f64, max_iteratio
         }
 
         if step % 1000 == 0 {
-            println!("Step: {}, max force: {}", step, max_force.length());
+            tracing::debug!(
+                step,
+                max_force = max_force.length(),
+                "Force simulation step"
+            );
+        }
+
+        if step % DEADLINE_CHECK_INTERVAL == 0
+            && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            break;
         }
 
         step += 1;
     }
-}
 
-fn cooling_factor(initial_temperature: f64, step: usize, max_iterations: usize) -> f64 {
-    let alpha = 1.0;
-    let beta = 1.0;
-    let gamma = 1.0;
-
-    initial_temperature * alpha
-        / (1.0 + beta * initial_temperature * step as f64 / max_iterations as f64).powf(gamma)
+    step
 }
 
-fn compute_force(graph: &Graph, layout: &Layout, node_id: NodeId) -> kurbo::Vec2 {
-    let repulsive = graph
-        .nodes()
+fn compute_force(
+    graph: &Graph,
+    layout: &Layout,
+    config: &LayoutConfig,
+    center: kurbo::Point,
+    node_ids: &[NodeId],
+    node_id: NodeId,
+) -> kurbo::Vec2 {
+    let parent = graph.node_parent(node_id);
+    let repulsive = node_ids
+        .iter()
+        .copied()
         .filter(|&other_id| other_id != node_id)
-        .map(|other_id| repulsive_force(layout, node_id, other_id))
+        .map(|other_id| {
+            let force = repulsive_force(layout, config, node_id, other_id);
+            if config.unrelated_repulsion == 1.0 {
+                return force;
+            }
+            let other_parent = graph.node_parent(other_id);
+            let related = (parent.is_some() && parent == other_parent)
+                || parent == Some(other_id)
+                || other_parent == Some(node_id);
+            if related {
+                force
+            } else {
+                config.unrelated_repulsion * force
+            }
+        })
         .reduce(|u, v| u + v)
         .unwrap_or_default();
 
     let attractive = graph
         .node_outgoing_edges(node_id)
-        .unwrap()
+        .unwrap_or_default()
         .iter()
-        .map(|&edge_id| attractive_force(layout, node_id, graph.edge(edge_id).unwrap().to))
+        .filter_map(|&edge_id| graph.edge(edge_id))
+        .filter(|edge| {
+            layout.rects.contains_key(&edge.to)
+                && config
+                    .relations
+                    .as_ref()
+                    .is_none_or(|relations| relations.contains(&edge.relation))
+        })
+        .map(|edge| {
+            attractive_force(
+                layout,
+                config.ideal_length(edge.relation),
+                config.attraction_strength,
+                node_id,
+                edge.to,
+            )
+        })
         .reduce(|u, v| u + v)
         .unwrap_or_default();
 
-    repulsive + attractive
+    let gravity = config.gravity * (center - layout.rects[&node_id].center());
+
+    repulsive + attractive + gravity
+}
+
+/// Limit a displacement to `max_length`, discarding it if it is not finite.
+fn clamp_displacement(displacement: kurbo::Vec2, max_length: f64) -> kurbo::Vec2 {
+    let length = displacement.hypot();
+    if !length.is_finite() {
+        kurbo::Vec2::ZERO
+    } else if length > max_length {
+        displacement * (max_length / length)
+    } else {
+        displacement
+    }
+}
+
+/// The mean of the centers of the given nodes.
+fn centroid(layout: &Layout, node_ids: &[NodeId]) -> kurbo::Point {
+    if node_ids.is_empty() {
+        return kurbo::Point::ZERO;
+    }
+    let sum = node_ids
+        .iter()
+        .map(|node_id| layout.rects[node_id].center().to_vec2())
+        .fold(kurbo::Vec2::ZERO, |u, v| u + v);
+    (sum / node_ids.len() as f64).to_point()
 }
 
 /// Compute the repulsive force between two nodes.
-fn repulsive_force(layout: &Layout, u: NodeId, v: NodeId) -> kurbo::Vec2 {
+fn repulsive_force(layout: &Layout, config: &LayoutConfig, u: NodeId, v: NodeId) -> kurbo::Vec2 {
     let pos_u = layout.rects[&u].center();
     let pos_v = layout.rects[&v].center();
 
     // Prevent division by zero
     let distance = pos_u.distance(pos_v).max(1e-6);
-    let force = IDEAL_SPRING_LENGTH.powi(2) / distance * (pos_u - pos_v) / distance;
+    let force = config.repulsion_strength * config.ideal_spring_length.powi(2) / distance
+        * (pos_u - pos_v)
+        / distance;
 
     if !force.is_finite() {
         return kurbo::Vec2::ZERO;
@@ -102,12 +630,19 @@ fn repulsive_force(layout: &Layout, u: NodeId, v: NodeId) -> kurbo::Vec2 {
     force
 }
 
-fn attractive_force(layout: &Layout, u: NodeId, v: NodeId) -> kurbo::Vec2 {
+/// Compute the attractive force along an edge with the given ideal length, scaled by `strength`.
+fn attractive_force(
+    layout: &Layout,
+    ideal_length: f64,
+    strength: f64,
+    u: NodeId,
+    v: NodeId,
+) -> kurbo::Vec2 {
     let pos_u = layout.rects[&u].center();
     let pos_v = layout.rects[&v].center();
 
     let distance = pos_u.distance(pos_v);
-    let force = (distance.powi(2) / IDEAL_SPRING_LENGTH) * (pos_v - pos_u);
+    let force = strength * (distance.powi(2) / ideal_length) * (pos_v - pos_u);
 
     // Limit the force to a maximum magnitude to prevent overflow
     let max_force_magnitude = 1000.0;
@@ -123,38 +658,621 @@ fn attractive_force(layout: &Layout, u: NodeId, v: NodeId) -> kurbo::Vec2 {
     clamped_force
 }
 
-fn initial_layout(graph: &Graph) -> Layout {
+fn initial_layout(graph: &Graph, config: &LayoutConfig) -> Layout {
     let mut layout = Layout {
         rects: HashMap::new(),
         lines: HashMap::new(),
+        arcs: HashMap::new(),
     };
 
-    let mut x = 0.0;
-    let mut y = 0.0;
+    let mut node_ids: Vec<_> = graph.nodes().collect();
+    node_ids.sort_unstable();
+    // Scatter the nodes over a square with room for a grid of all of them
+    let side = GRID_SPACING * (node_ids.len() as f64).sqrt();
+    let mut rng = config.seed.map(StdRng::seed_from_u64);
 
-    for node_id in graph.nodes() {
+    for (i, node_id) in node_ids.into_iter().enumerate() {
+        let origin = match &mut rng {
+            Some(rng) => kurbo::Point::new(rng.gen_range(0.0..side), rng.gen_range(0.0..side)),
+            None => kurbo::Point::new(i as f64 * GRID_SPACING, i as f64 * GRID_SPACING),
+        };
         layout.rects.insert(
             node_id,
-            kurbo::Rect::from_origin_size((x, y), (64.0, 100.0)),
+            kurbo::Rect::from_origin_size(origin, config.node_size(graph, node_id)),
         );
-
-        x += 150.0;
-        y += 150.0;
     }
 
-    layout_edges(graph, &mut layout);
-
+    // The edges are laid out once the nodes have their final positions
     layout
 }
 
+/// Lay out the edges between nodes with rectangles. Edges with an endpoint
+/// that has no rectangle, e.g. while the graph is being updated, are skipped.
+/// Called once per layout, so that skipped edges are reported once.
 fn layout_edges(graph: &Graph, layout: &mut Layout) {
+    // Group edges between the same pair of nodes, regardless of direction
+    let mut edges_between: HashMap<(NodeId, NodeId), Vec<EdgeId>> = HashMap::new();
+    let mut skipped = 0;
     for edge_id in graph.edges() {
-        layout.lines.insert(
-            edge_id,
-            kurbo::Line::new(
-                layout.rects[&graph.edge(edge_id).unwrap().from].center(),
-                layout.rects[&graph.edge(edge_id).unwrap().to].center(),
+        let edge = graph.edge(edge_id).unwrap();
+        if !layout.rects.contains_key(&edge.from) || !layout.rects.contains_key(&edge.to) {
+            skipped += 1;
+            continue;
+        }
+        let key = (edge.from.min(edge.to), edge.from.max(edge.to));
+        edges_between.entry(key).or_default().push(edge_id);
+    }
+    if skipped > 0 {
+        tracing::warn!(skipped, "Skipped edges whose nodes have no position");
+    }
+
+    for ((u, v), mut edge_ids) in edges_between {
+        edge_ids.sort_unstable();
+
+        if u == v {
+            let rect = layout.rects[&u];
+            for (i, edge_id) in edge_ids.into_iter().enumerate() {
+                let radius = SELF_LOOP_RADIUS * (i + 1) as f64;
+                layout.arcs.insert(
+                    edge_id,
+                    kurbo::Arc {
+                        center: kurbo::Point::new(rect.x1, rect.y0),
+                        radii: kurbo::Vec2::new(radius, radius),
+                        // Sweep around the outside of the corner
+                        start_angle: std::f64::consts::PI,
+                        sweep_angle: 1.5 * std::f64::consts::PI,
+                        x_rotation: 0.0,
+                    },
+                );
+            }
+            continue;
+        }
+
+        let direction = layout.rects[&v].center() - layout.rects[&u].center();
+        let length = direction.hypot();
+        let normal = if length > 0.0 {
+            kurbo::Vec2::new(-direction.y, direction.x) / length
+        } else {
+            kurbo::Vec2::ZERO
+        };
+
+        // Offset parallel edges symmetrically around the center line
+        let count = edge_ids.len();
+        for (i, edge_id) in edge_ids.into_iter().enumerate() {
+            let offset = (i as f64 - (count - 1) as f64 / 2.0) * PARALLEL_EDGE_SPACING * normal;
+            let edge = graph.edge(edge_id).unwrap();
+            layout.lines.insert(
+                edge_id,
+                kurbo::Line::new(
+                    layout.rects[&edge.from].center() + offset,
+                    layout.rects[&edge.to].center() + offset,
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EdgeData, NodeContents, NodeData};
+
+    fn item(i: usize) -> NodeData {
+        NodeData::new(NodeContents::Item {
+            display_name: format!("item{i}"),
+            moniker: None,
+            range: None,
+            selection_range: None,
+            doc: None,
+            kind: None,
+        })
+    }
+
+    /// A graph with `count` unconnected item nodes.
+    fn graph_with_items(count: usize) -> Graph {
+        let mut graph = Graph::default();
+        for i in 0..count {
+            graph.add_node(item(i));
+        }
+        graph
+    }
+
+    /// A layout with two nodes whose centers are `distance` apart.
+    fn two_nodes_apart(distance: f64) -> Layout {
+        let mut layout = Layout {
+            rects: HashMap::new(),
+            lines: HashMap::new(),
+            arcs: HashMap::new(),
+        };
+        layout
+            .rects
+            .insert(0, kurbo::Rect::from_origin_size((0.0, 0.0), (10.0, 10.0)));
+        layout.rects.insert(
+            1,
+            kurbo::Rect::from_origin_size((distance, 0.0), (10.0, 10.0)),
+        );
+        layout
+    }
+
+    #[test]
+    fn ideal_length_prefers_the_relation_override() {
+        let mut config = LayoutConfig::default();
+        assert_eq!(
+            config.ideal_length(Relation::IsParentOf),
+            IDEAL_SPRING_LENGTH
+        );
+
+        config
+            .relation_spring_lengths
+            .insert(Relation::IsParentOf, 120.0);
+        assert_eq!(config.ideal_length(Relation::IsParentOf), 120.0);
+    }
+
+    #[test]
+    fn longer_ideal_length_weakens_attraction() {
+        let layout = two_nodes_apart(20.0);
+        let short = attractive_force(&layout, 25.0, 1.0, 0, 1);
+        let long = attractive_force(&layout, 100.0, 1.0, 0, 1);
+        assert!(short.x > 0.0);
+        assert!(long.x > 0.0);
+        assert!(long.length() < short.length());
+    }
+
+    #[test]
+    fn stronger_repulsion_spreads_the_layout() {
+        let mut graph = graph_with_items(4);
+        for child in 1..4 {
+            add_parent_edge(&mut graph, 0, child);
+        }
+        // The mean distance of the leaves from the hub after a fixed number of
+        // iterations, since the simulation of this star does not settle
+        let spread = |config: &LayoutConfig| {
+            let mut layout = initial_layout(&graph, config);
+            apply_forces(&graph, &mut layout, config, 0.1, 200, None);
+            let hub = layout.rects[&0].center();
+            let total: f64 = (1..4)
+                .map(|leaf| layout.rects[&leaf].center().distance(hub))
+                .sum();
+            total / 3.0
+        };
+
+        let strong = LayoutConfig {
+            repulsion_strength: 4.0,
+            ..LayoutConfig::default()
+        };
+        assert!(spread(&strong) > spread(&LayoutConfig::default()));
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_layout() {
+        let mut graph = graph_with_items(4);
+        for child in 1..4 {
+            add_parent_edge(&mut graph, 0, child);
+        }
+        let positions = |seed| {
+            let config = LayoutConfig {
+                seed: Some(seed),
+                ..LayoutConfig::default()
+            };
+            let mut rects: Vec<_> = Layout::compute_with_config(&graph, &config)
+                .rects()
+                .collect();
+            rects.sort_unstable_by_key(|&(node_id, _)| node_id);
+            rects
+        };
+
+        assert_eq!(positions(7), positions(7));
+        assert_ne!(positions(7), positions(8));
+    }
+
+    #[test]
+    fn displacement_is_clamped_and_must_be_finite() {
+        let clamped = clamp_displacement(kurbo::Vec2::new(30.0, 40.0), 10.0);
+        assert!((clamped - kurbo::Vec2::new(6.0, 8.0)).hypot() < 1e-9);
+        let small = kurbo::Vec2::new(1.0, 1.0);
+        assert_eq!(clamp_displacement(small, 10.0), small);
+        let infinite = kurbo::Vec2::new(f64::INFINITY, 0.0);
+        assert_eq!(clamp_displacement(infinite, 10.0), kurbo::Vec2::ZERO);
+    }
+
+    #[test]
+    fn dense_graph_with_a_hot_start_stays_finite() {
+        let count = 20;
+        let mut graph = graph_with_items(count);
+        for from in 0..count {
+            for to in from + 1..count {
+                add_parent_edge(&mut graph, from, to);
+            }
+        }
+        let config = LayoutConfig {
+            initial_temperature: 1e6,
+            max_displacement: 50.0,
+            // The first steps are the ones that would blow up
+            max_duration: Some(Duration::from_secs(1)),
+            ..LayoutConfig::default()
+        };
+
+        let layout = Layout::compute_with_config(&graph, &config);
+        assert_eq!(layout.rects().count(), count);
+        assert!(layout.rects().all(|(_, rect)| rect.x0.is_finite()
+            && rect.y0.is_finite()
+            && rect.x1.is_finite()
+            && rect.y1.is_finite()));
+    }
+
+    #[test]
+    fn positions_are_read_through_the_geometry_types() {
+        let mut graph = graph_with_items(2);
+        let edge = add_parent_edge(&mut graph, 0, 1);
+        let layout = Layout::compute(&graph);
+
+        let first: Rect = layout.rect(0).unwrap();
+        let second: Rect = layout.rect(1).unwrap();
+        let line: Line = layout.line(edge).unwrap();
+        assert_eq!(line.p0, first.center());
+        assert_eq!(line.p1, second.center());
+        assert_eq!(layout.rects().count(), 2);
+        assert!(layout.rect(2).is_none());
+        assert!(layout.arc(edge).is_none());
+    }
+
+    #[test]
+    fn edges_to_nodes_without_a_rect_are_skipped() {
+        let mut graph = graph_with_items(2);
+        let placed = add_parent_edge(&mut graph, 0, 1);
+        let mut layout = Layout::grid(&graph, 2);
+        let unplaced = graph.add_node(item(2));
+        let dangling = add_parent_edge(&mut graph, 0, unplaced);
+
+        layout.lines.clear();
+        layout_edges(&graph, &mut layout);
+        assert!(layout.line(placed).is_some());
+        assert!(layout.line(dangling).is_none());
+
+        let placed_ids = [0, 1];
+        let center = centroid(&layout, &placed_ids);
+        let config = LayoutConfig::default();
+        let force = compute_force(&graph, &layout, &config, center, &placed_ids, 0);
+        assert!(force.is_finite());
+        apply_forces(&graph, &mut layout, &LayoutConfig::default(), 0.1, 10, None);
+        assert!(layout.rect(unplaced).is_none());
+    }
+
+    #[test]
+    fn orphan_edges_do_not_break_the_layout() {
+        let nodes = (0..2).map(|i| (i, item(i))).collect();
+        let edges = HashMap::from([
+            (
+                0,
+                EdgeData {
+                    from: 0,
+                    to: 1,
+                    relation: Relation::IsParentOf,
+                },
+            ),
+            (
+                1,
+                EdgeData {
+                    from: 1,
+                    to: 7,
+                    relation: Relation::IsParentOf,
+                },
             ),
+        ]);
+        let graph = Graph::from_parts(nodes, edges);
+
+        let layout = Layout::compute(&graph);
+        assert_eq!(layout.rects().count(), 2);
+        assert!(layout.line(0).is_some());
+        assert!(layout.line(1).is_none());
+    }
+
+    #[test]
+    fn grid_places_nodes_in_rows() {
+        let graph = graph_with_items(5);
+        let layout = Layout::grid(&graph, 2);
+        let origin = |id: NodeId| layout.rects[&id].origin();
+        assert_eq!(origin(0), kurbo::Point::new(0.0, 0.0));
+        assert_eq!(origin(1), kurbo::Point::new(GRID_SPACING, 0.0));
+        assert_eq!(origin(2), kurbo::Point::new(0.0, GRID_SPACING));
+        assert_eq!(origin(4), kurbo::Point::new(0.0, 2.0 * GRID_SPACING));
+    }
+
+    #[test]
+    fn huge_graphs_fall_back_to_a_grid() {
+        let graph = graph_with_items(9);
+        let config = LayoutConfig {
+            grid_fallback_threshold: Some(8),
+            ..LayoutConfig::default()
+        };
+        let layout = Layout::compute_with_config(&graph, &config);
+        let grid = Layout::grid(&graph, 3);
+        for node_id in graph.nodes() {
+            assert_eq!(layout.rects[&node_id], grid.rects[&node_id]);
+        }
+    }
+
+    fn add_parent_edge(graph: &mut Graph, from: NodeId, to: NodeId) -> EdgeId {
+        graph.add_edge(EdgeData {
+            from,
+            to,
+            relation: Relation::IsParentOf,
+        })
+    }
+
+    #[test]
+    fn parallel_edges_are_offset_symmetrically() {
+        let mut graph = graph_with_items(2);
+        let forward = add_parent_edge(&mut graph, 0, 1);
+        let backward = add_parent_edge(&mut graph, 1, 0);
+        let layout = Layout::grid(&graph, 2);
+
+        let center_y = layout.rects[&0].center().y;
+        let forward = layout.lines[&forward];
+        let backward = layout.lines[&backward];
+        assert_eq!(forward.p0.y, center_y - PARALLEL_EDGE_SPACING / 2.0);
+        assert_eq!(backward.p0.y, center_y + PARALLEL_EDGE_SPACING / 2.0);
+        assert_eq!(forward.p0.x, backward.p1.x);
+    }
+
+    #[test]
+    fn self_loops_are_drawn_as_nested_arcs() {
+        let mut graph = graph_with_items(1);
+        let first = add_parent_edge(&mut graph, 0, 0);
+        let second = add_parent_edge(&mut graph, 0, 0);
+        let layout = Layout::grid(&graph, 1);
+
+        assert!(layout.lines.is_empty());
+        let corner = kurbo::Point::new(layout.rects[&0].x1, layout.rects[&0].y0);
+        assert_eq!(layout.arcs[&first].center, corner);
+        assert_eq!(layout.arcs[&first].radii.x, SELF_LOOP_RADIUS);
+        assert_eq!(layout.arcs[&second].radii.x, 2.0 * SELF_LOOP_RADIUS);
+    }
+
+    #[test]
+    fn node_size_uses_the_text_measurement() {
+        let mut graph = graph_with_items(1);
+        graph.rename_node(0, "a_rather_long_function_name".to_owned());
+
+        let config = LayoutConfig::default();
+        let size = config.node_size(&graph, 0);
+        assert_eq!(size.width, 27.0 * CHAR_WIDTH + 2.0 * NODE_PADDING);
+        assert_eq!(size.height, NODE_HEIGHT);
+
+        let config = LayoutConfig {
+            measure_text: Some(Arc::new(|text: &str| text.len() as f64 * 10.0)),
+            ..LayoutConfig::default()
+        };
+        assert_eq!(
+            config.node_size(&graph, 0).width,
+            270.0 + 2.0 * NODE_PADDING
+        );
+
+        let config = LayoutConfig {
+            measure_text: Some(Arc::new(|_: &str| 0.0)),
+            ..LayoutConfig::default()
+        };
+        assert_eq!(config.node_size(&graph, 0).width, NODE_WIDTH);
+    }
+
+    #[test]
+    fn grid_columns_fit_the_widest_node() {
+        let graph = graph_with_items(2);
+        let config = LayoutConfig {
+            measure_text: Some(Arc::new(|_: &str| 300.0)),
+            ..LayoutConfig::default()
+        };
+        let layout = Layout::grid_with_config(&graph, 2, &config);
+        assert!(!layout.rects[&0].overlaps(layout.rects[&1]));
+        assert_eq!(layout.rects[&1].x0 - layout.rects[&0].x1, GRID_GAP);
+    }
+
+    #[test]
+    fn trace_halves_its_resolution_at_the_cap() {
+        let layout = two_nodes_apart(100.0);
+        let mut trace = Trace {
+            frames: vec![LayoutFrame::capture(0, &layout)],
+            interval: 10,
+            max_frames: 4,
+        };
+        for step in 1..=100 {
+            trace.record(step, &layout);
+        }
+
+        let steps: Vec<_> = trace.frames.iter().map(|frame| frame.step).collect();
+        assert_eq!(steps, vec![0, 40, 80]);
+        assert_eq!(trace.interval, 40);
+    }
+
+    #[test]
+    fn trace_ends_with_the_final_positions() {
+        let mut graph = graph_with_items(3);
+        add_parent_edge(&mut graph, 1, 0);
+        add_parent_edge(&mut graph, 2, 0);
+        let config = LayoutConfig {
+            max_trace_frames: 5,
+            ..LayoutConfig::default()
+        };
+        let (layout, frames) = Layout::compute_with_trace(&graph, &config);
+
+        assert!(frames.len() <= 5);
+        assert_eq!(frames[0].step, 0);
+        let last = frames.last().unwrap();
+        for (node_id, rect) in layout.rects() {
+            assert_eq!(last.positions[&node_id], rect.origin());
+        }
+    }
+
+    #[test]
+    fn centroid_is_the_mean_of_node_centers() {
+        let layout = two_nodes_apart(100.0);
+        assert_eq!(centroid(&layout, &[0, 1]), kurbo::Point::new(55.0, 5.0));
+        assert_eq!(centroid(&layout, &[0]), kurbo::Point::new(5.0, 5.0));
+    }
+
+    #[test]
+    fn gravity_pulls_a_lone_node_towards_the_center() {
+        let graph = graph_with_items(1);
+        let mut layout = two_nodes_apart(100.0);
+        layout.rects.remove(&1);
+        let config = LayoutConfig {
+            gravity: 0.5,
+            ..LayoutConfig::default()
+        };
+
+        let center = kurbo::Point::new(25.0, 5.0);
+        let force = compute_force(&graph, &layout, &config, center, &[0], 0);
+        assert_eq!(force, kurbo::Vec2::new(10.0, 0.0));
+    }
+
+    #[test]
+    fn cooling_schedules_decrease_the_temperature() {
+        let linear = CoolingSchedule::Linear;
+        assert_eq!(linear.factor(2.0, 0, 100), 2.0);
+        assert_eq!(linear.factor(2.0, 50, 100), 1.0);
+        assert_eq!(linear.factor(2.0, 150, 100), 0.0);
+
+        let exponential = CoolingSchedule::Exponential { rate: 0.5 };
+        assert_eq!(exponential.factor(1.0, 3, 100), 0.125);
+
+        let inverse_time = CoolingSchedule::default();
+        assert_eq!(inverse_time.factor(1.0, 0, 100), 1.0);
+        assert_eq!(inverse_time.factor(1.0, 100, 100), 0.5);
+    }
+
+    #[test]
+    fn excluded_relations_do_not_attract() {
+        let unconnected = graph_with_items(2);
+        let mut connected = graph_with_items(2);
+        add_parent_edge(&mut connected, 0, 1);
+        let layout = two_nodes_apart(200.0);
+        let center = centroid(&layout, &[0, 1]);
+
+        let all = LayoutConfig::default();
+        let none = LayoutConfig {
+            relations: Some(HashSet::new()),
+            ..LayoutConfig::default()
+        };
+        let repulsion_only = compute_force(&unconnected, &layout, &all, center, &[0, 1], 0);
+        assert_ne!(
+            compute_force(&connected, &layout, &all, center, &[0, 1], 0),
+            repulsion_only
+        );
+        assert_eq!(
+            compute_force(&connected, &layout, &none, center, &[0, 1], 0),
+            repulsion_only
         );
     }
+
+    #[test]
+    fn max_duration_stops_the_simulation_early() {
+        let mut graph = graph_with_items(3);
+        for child in 1..3 {
+            add_parent_edge(&mut graph, 0, child);
+        }
+        let expired = LayoutConfig {
+            max_duration: Some(Duration::ZERO),
+            ..LayoutConfig::default()
+        };
+        let (_, frames) = Layout::compute_with_trace(&graph, &expired);
+        assert_eq!(frames.last().unwrap().step, DEADLINE_CHECK_INTERVAL);
+
+        let unbounded = LayoutConfig {
+            max_duration: Some(Duration::MAX),
+            ..LayoutConfig::default()
+        };
+        let (_, frames) = Layout::compute_with_trace(&graph, &unbounded);
+        assert!(frames.last().unwrap().step > DEADLINE_CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn quality_counts_crossings_and_overlaps() {
+        let mut graph = graph_with_items(4);
+        let first = add_parent_edge(&mut graph, 0, 1);
+        let second = add_parent_edge(&mut graph, 2, 3);
+        let mut layout = Layout {
+            rects: HashMap::new(),
+            lines: HashMap::new(),
+            arcs: HashMap::new(),
+        };
+        let square = |x, y| kurbo::Rect::from_origin_size((x, y), (10.0, 10.0));
+        layout.rects.insert(0, square(0.0, 0.0));
+        layout.rects.insert(1, square(5.0, 5.0));
+        layout.rects.insert(2, square(100.0, 0.0));
+        layout.rects.insert(3, square(200.0, 0.0));
+        layout
+            .lines
+            .insert(first, kurbo::Line::new((0.0, 0.0), (10.0, 10.0)));
+        layout
+            .lines
+            .insert(second, kurbo::Line::new((0.0, 10.0), (10.0, 0.0)));
+
+        let quality = layout.quality(&graph);
+        assert_eq!(quality.edge_crossings, 1);
+        assert_eq!(quality.node_overlaps, 1);
+        assert_eq!(quality.average_edge_length, 200.0_f64.sqrt());
+    }
+
+    #[test]
+    fn edges_sharing_a_node_do_not_cross() {
+        let mut graph = graph_with_items(3);
+        let first = add_parent_edge(&mut graph, 0, 1);
+        let second = add_parent_edge(&mut graph, 0, 2);
+        let layout = Layout::grid(&graph, 3);
+        assert!(layout.lines.contains_key(&first));
+        assert!(layout.lines.contains_key(&second));
+
+        let quality = layout.quality(&graph);
+        assert_eq!(quality.edge_crossings, 0);
+        assert_eq!(quality.node_overlaps, 0);
+    }
+
+    #[test]
+    fn unrelated_repulsion_scales_only_unrelated_nodes() {
+        let layout = two_nodes_apart(100.0);
+        let center = layout.rects[&0].center();
+        let full = LayoutConfig {
+            gravity: 0.0,
+            ..LayoutConfig::default()
+        };
+        let halved = LayoutConfig {
+            unrelated_repulsion: 0.5,
+            ..full.clone()
+        };
+
+        let mut graph = graph_with_items(2);
+        let unrelated = compute_force(&graph, &layout, &full, center, &[0, 1], 0);
+        assert!(unrelated.x < 0.0);
+        assert_eq!(
+            compute_force(&graph, &layout, &halved, center, &[0, 1], 0),
+            0.5 * unrelated
+        );
+
+        add_parent_edge(&mut graph, 0, 1);
+        assert_eq!(
+            compute_force(&graph, &layout, &halved, center, &[0, 1], 0),
+            compute_force(&graph, &layout, &full, center, &[0, 1], 0)
+        );
+    }
+
+    #[test]
+    fn from_positions_starts_at_the_saved_origins() {
+        let graph = graph_with_items(1);
+        let positions = HashMap::from([(0, (40.0, -20.0))]);
+        let layout = Layout::from_positions(&graph, &positions, &LayoutConfig::default());
+        assert_eq!(layout.rects[&0].origin(), kurbo::Point::new(40.0, -20.0));
+    }
+
+    #[test]
+    fn from_positions_places_new_nodes_and_edges() {
+        let mut graph = graph_with_items(2);
+        let edge = add_parent_edge(&mut graph, 0, 1);
+        let positions = HashMap::from([(0, (0.0, 0.0))]);
+        let layout = Layout::from_positions(&graph, &positions, &LayoutConfig::default());
+
+        assert_eq!(layout.rects.len(), 2);
+        assert!(layout.lines.contains_key(&edge));
+        let distance = (layout.rects[&1].center() - layout.rects[&0].center()).hypot();
+        assert!(distance > 0.0);
+    }
 }
@@ -1,5 +1,20 @@
+#[cfg(feature = "binary")]
+mod binary;
+mod d3;
+mod dot;
+#[cfg(feature = "layout")]
+mod geometry;
+mod graphml;
+mod jsonl;
+#[cfg(feature = "layout")]
 mod layout;
+mod matrix;
 mod types;
+mod validate;
 
+#[cfg(feature = "layout")]
+pub use geometry::*;
+#[cfg(feature = "layout")]
 pub use layout::*;
 pub use types::*;
+pub use validate::*;
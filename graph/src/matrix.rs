@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use crate::{Graph, NodeId, Relation};
+
+impl Graph {
+    /// The node IDs in ascending order and a 0/1 matrix indexed by that order,
+    /// where `matrix[i][j]` is 1 if there is an edge from node `i` to node `j`.
+    pub fn to_adjacency_matrix(&self) -> (Vec<NodeId>, Vec<Vec<u8>>) {
+        self.adjacency_matrix(None)
+    }
+
+    /// Like [`Graph::to_adjacency_matrix`], but only considering edges of `relation`.
+    pub fn to_adjacency_matrix_of_relation(
+        &self,
+        relation: Relation,
+    ) -> (Vec<NodeId>, Vec<Vec<u8>>) {
+        self.adjacency_matrix(Some(relation))
+    }
+
+    fn adjacency_matrix(&self, relation: Option<Relation>) -> (Vec<NodeId>, Vec<Vec<u8>>) {
+        let mut node_ids: Vec<_> = self.nodes().collect();
+        node_ids.sort_unstable();
+        let index: HashMap<NodeId, usize> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, &node_id)| (node_id, i))
+            .collect();
+
+        let mut matrix = vec![vec![0; node_ids.len()]; node_ids.len()];
+        for edge_id in self.edges() {
+            let edge = self.edge(edge_id).unwrap();
+            if relation.is_some_and(|relation| edge.relation != relation) {
+                continue;
+            }
+            // Skip edges whose endpoints were removed
+            let (Some(&from), Some(&to)) = (index.get(&edge.from), index.get(&edge.to)) else {
+                continue;
+            };
+            matrix[from][to] = 1;
+        }
+        (node_ids, matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{EdgeData, NodeContents, NodeData};
+
+    #[test]
+    fn adjacency_matrix_skips_orphan_edges() {
+        let mut graph = Graph::default();
+        let folder = graph.add_node(NodeData::new(NodeContents::Folder {
+            display_name: "src".to_owned(),
+            path: PathBuf::from("src"),
+        }));
+        let file = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "main.rs".to_owned(),
+            path: PathBuf::from("src/main.rs"),
+        }));
+        for to in [file, file + 1] {
+            graph.add_edge(EdgeData {
+                from: folder,
+                to,
+                relation: Relation::IsParentOf,
+            });
+        }
+
+        let (node_ids, matrix) = graph.to_adjacency_matrix();
+        assert_eq!(node_ids, vec![folder, file]);
+        assert_eq!(matrix, vec![vec![0, 1], vec![0, 0]]);
+        assert_eq!(
+            graph.to_adjacency_matrix_of_relation(Relation::IsParentOf),
+            (node_ids, matrix)
+        );
+    }
+}
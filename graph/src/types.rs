@@ -1,4 +1,7 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -21,9 +24,47 @@ pub struct Graph {
     last_node_id: NodeId,
     /// The next edge ID to be used.
     last_edge_id: EdgeId,
+    /// The directory that paths were made relative to by [`Graph::make_paths_relative`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) path_root: Option<PathBuf>,
 }
 
 impl Graph {
+    /// Build a graph from nodes and edges with preassigned IDs.
+    pub(crate) fn from_parts(
+        nodes: HashMap<NodeId, NodeData>,
+        edges: HashMap<EdgeId, EdgeData>,
+    ) -> Self {
+        let mut graph = Graph {
+            nodes,
+            edges,
+            ..Default::default()
+        };
+        graph.reindex();
+        graph
+    }
+
+    /// Rebuild the adjacency maps and ID counters from the nodes and edges.
+    pub(crate) fn reindex(&mut self) {
+        self.nodes_to_outgoing_edges = self.nodes.keys().map(|&id| (id, Vec::new())).collect();
+        self.nodes_to_incoming_edges = self.nodes.keys().map(|&id| (id, Vec::new())).collect();
+        let mut edge_ids: Vec<_> = self.edges.keys().copied().collect();
+        edge_ids.sort_unstable();
+        for edge_id in edge_ids {
+            let EdgeData { from, to, .. } = self.edges[&edge_id];
+            self.nodes_to_outgoing_edges
+                .entry(from)
+                .or_default()
+                .push(edge_id);
+            self.nodes_to_incoming_edges
+                .entry(to)
+                .or_default()
+                .push(edge_id);
+        }
+        self.last_node_id = self.nodes.keys().max().map_or(0, |&id| id + 1);
+        self.last_edge_id = self.edges.keys().max().map_or(0, |&id| id + 1);
+    }
+
     pub fn add_node(&mut self, node: NodeData) -> NodeId {
         let id = self.fresh_node_id();
         self.nodes.insert(id, node);
@@ -44,6 +85,234 @@ impl Graph {
         id
     }
 
+    /// Add an edge unless an edge with the same endpoints and relation already
+    /// exists, in which case the ID of the existing edge is returned.
+    pub fn add_edge_deduped(&mut self, edge: EdgeData) -> EdgeId {
+        let existing = self
+            .edges_between(edge.from, edge.to)
+            .into_iter()
+            .find(|id| self.edges[id].relation == edge.relation);
+        match existing {
+            Some(id) => id,
+            None => self.add_edge(edge),
+        }
+    }
+
+    /// Remove a node together with all edges incident to it.
+    pub fn remove_node(&mut self, id: NodeId) -> Option<NodeData> {
+        let node = self.nodes.remove(&id)?;
+        let outgoing = self.nodes_to_outgoing_edges.remove(&id).unwrap_or_default();
+        let incoming = self.nodes_to_incoming_edges.remove(&id).unwrap_or_default();
+        for edge_id in outgoing.into_iter().chain(incoming) {
+            self.remove_edge(edge_id);
+        }
+        Some(node)
+    }
+
+    pub fn remove_edge(&mut self, id: EdgeId) -> Option<EdgeData> {
+        let edge = self.edges.remove(&id)?;
+        if let Some(outgoing) = self.nodes_to_outgoing_edges.get_mut(&edge.from) {
+            outgoing.retain(|&edge_id| edge_id != id);
+        }
+        if let Some(incoming) = self.nodes_to_incoming_edges.get_mut(&edge.to) {
+            incoming.retain(|&edge_id| edge_id != id);
+        }
+        Some(edge)
+    }
+
+    /// Remove all `IsParentOf` descendants of `parent` and add `new_children` as its
+    /// children instead. Returns the IDs of the new children, in order.
+    pub fn replace_subtree(
+        &mut self,
+        parent: NodeId,
+        new_children: impl Iterator<Item = NodeData>,
+    ) -> Vec<NodeId> {
+        for descendant in self.descendants(parent) {
+            self.remove_node(descendant);
+        }
+        new_children
+            .map(|child| {
+                let child_id = self.add_node(child);
+                self.add_edge(EdgeData {
+                    from: parent,
+                    to: child_id,
+                    relation: Relation::IsParentOf,
+                });
+                child_id
+            })
+            .collect()
+    }
+
+    /// Store the paths of folders, files and crates below `root` relative to it, so that
+    /// the serialized graph does not depend on where it was analyzed. Paths outside of
+    /// `root` stay absolute.
+    pub fn make_paths_relative(&mut self, root: &Path) {
+        for node in self.nodes.values_mut() {
+            if let Some(path) = node.contents.path_mut() {
+                if let Ok(relative) = path.strip_prefix(root) {
+                    *path = relative.to_owned();
+                }
+            }
+        }
+        self.path_root = Some(root.to_owned());
+    }
+
+    /// Resolve relative paths against `root`, e.g. the [`Graph::path_root`] of a loaded
+    /// graph or the location of the workspace on this machine.
+    pub fn make_paths_absolute(&mut self, root: &Path) {
+        for node in self.nodes.values_mut() {
+            if let Some(path) = node.contents.path_mut() {
+                if path.is_relative() {
+                    *path = root.join(&*path);
+                }
+            }
+        }
+        self.path_root = None;
+    }
+
+    /// The directory the paths are relative to, if [`Graph::make_paths_relative`] was used.
+    pub fn path_root(&self) -> Option<&Path> {
+        self.path_root.as_deref()
+    }
+
+    /// Remove all edges whose relation is not in `relations`, keeping all nodes.
+    pub fn retain_relations(&mut self, relations: &HashSet<Relation>) {
+        let removed: Vec<_> = self
+            .edges
+            .iter()
+            .filter(|(_, edge)| !relations.contains(&edge.relation))
+            .map(|(&edge_id, _)| edge_id)
+            .collect();
+        for edge_id in removed {
+            self.remove_edge(edge_id);
+        }
+    }
+
+    /// Remove all nodes that are not reachable from `roots` via outgoing edges.
+    pub fn retain_reachable(&mut self, roots: &[NodeId]) {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<_> = roots
+            .iter()
+            .copied()
+            .filter(|root| self.nodes.contains_key(root))
+            .collect();
+        while let Some(node_id) = stack.pop() {
+            if reachable.insert(node_id) {
+                stack.extend(self.node_neighbors(node_id).unwrap_or_default());
+            }
+        }
+        let unreachable: Vec<_> = self
+            .nodes
+            .keys()
+            .copied()
+            .filter(|node_id| !reachable.contains(node_id))
+            .collect();
+        for node_id in unreachable {
+            self.remove_node(node_id);
+        }
+    }
+
+    /// Remove edges of `relation` that are implied by a longer path of such edges,
+    /// such as a grandparent to grandchild shortcut. Reachability is preserved.
+    pub fn transitive_reduction(&mut self, relation: Relation) {
+        let mut edge_ids: Vec<_> = self.edges_of_relation(relation).collect();
+        edge_ids.sort_unstable();
+        for edge_id in edge_ids {
+            let EdgeData { from, to, .. } = self.edges[&edge_id];
+            if from != to && self.reachable_without(from, to, relation, edge_id) {
+                self.remove_edge(edge_id);
+            }
+        }
+    }
+
+    /// Whether `to` is reachable from `from` via edges of `relation` other than `skipped`.
+    fn reachable_without(
+        &self,
+        from: NodeId,
+        to: NodeId,
+        relation: Relation,
+        skipped: EdgeId,
+    ) -> bool {
+        let mut visited = HashSet::from([from]);
+        let mut stack = vec![from];
+        while let Some(node_id) = stack.pop() {
+            for &edge_id in self.node_outgoing_edges(node_id).unwrap_or_default() {
+                let edge = &self.edges[&edge_id];
+                if edge_id == skipped || edge.relation != relation {
+                    continue;
+                }
+                if edge.to == to {
+                    return true;
+                }
+                if visited.insert(edge.to) {
+                    stack.push(edge.to);
+                }
+            }
+        }
+        false
+    }
+
+    /// Reassign node and edge IDs to the contiguous ranges `0..n`, preserving their order.
+    /// Edges referencing missing nodes are removed. Returns the mapping from old to new IDs.
+    pub fn compact(&mut self) -> IdMapping {
+        let orphan_edges: Vec<_> = self
+            .edges
+            .iter()
+            .filter(|(_, edge)| {
+                !self.nodes.contains_key(&edge.from) || !self.nodes.contains_key(&edge.to)
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        for edge_id in orphan_edges {
+            self.remove_edge(edge_id);
+        }
+        let mut node_ids: Vec<_> = self.nodes.keys().copied().collect();
+        node_ids.sort_unstable();
+        let nodes: HashMap<NodeId, NodeId> = node_ids
+            .into_iter()
+            .enumerate()
+            .map(|(new_id, old_id)| (old_id, new_id))
+            .collect();
+
+        let mut edge_ids: Vec<_> = self.edges.keys().copied().collect();
+        edge_ids.sort_unstable();
+        let edges: HashMap<EdgeId, EdgeId> = edge_ids
+            .into_iter()
+            .enumerate()
+            .map(|(new_id, old_id)| (old_id, new_id))
+            .collect();
+
+        self.nodes = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(|(id, node)| (nodes[&id], node))
+            .collect();
+        self.edges = std::mem::take(&mut self.edges)
+            .into_iter()
+            .map(|(id, mut edge)| {
+                edge.from = nodes[&edge.from];
+                edge.to = nodes[&edge.to];
+                (edges[&id], edge)
+            })
+            .collect();
+        let remap_adjacency = |adjacency: HashMap<NodeId, Vec<EdgeId>>| {
+            adjacency
+                .into_iter()
+                .filter_map(|(node_id, edge_ids)| {
+                    let edge_ids = edge_ids.iter().map(|edge_id| edges[edge_id]).collect();
+                    Some((*nodes.get(&node_id)?, edge_ids))
+                })
+                .collect()
+        };
+        self.nodes_to_outgoing_edges =
+            remap_adjacency(std::mem::take(&mut self.nodes_to_outgoing_edges));
+        self.nodes_to_incoming_edges =
+            remap_adjacency(std::mem::take(&mut self.nodes_to_incoming_edges));
+        self.last_node_id = self.nodes.len();
+        self.last_edge_id = self.edges.len();
+
+        IdMapping { nodes, edges }
+    }
+
     pub fn node<N: Into<NodeId>>(&self, id: N) -> Option<&NodeData> {
         let id = id.into();
         self.nodes.get(&id)
@@ -64,6 +333,14 @@ impl Graph {
         self.edges.get_mut(&id)
     }
 
+    pub fn contains_node(&self, id: NodeId) -> bool {
+        self.nodes.contains_key(&id)
+    }
+
+    pub fn contains_edge(&self, id: EdgeId) -> bool {
+        self.edges.contains_key(&id)
+    }
+
     pub fn node_outgoing_edges(&self, id: NodeId) -> Option<&[EdgeId]> {
         self.nodes_to_outgoing_edges.get(&id).map(|v| v.as_slice())
     }
@@ -72,6 +349,52 @@ impl Graph {
         self.nodes_to_incoming_edges.get(&id).map(|v| v.as_slice())
     }
 
+    /// The edges going from `from` to `to`, of any relation.
+    pub fn edges_between(&self, from: NodeId, to: NodeId) -> Vec<EdgeId> {
+        self.node_outgoing_edges(from)
+            .unwrap_or_default()
+            .iter()
+            .copied()
+            .filter(|edge_id| self.edges[edge_id].to == to)
+            .collect()
+    }
+
+    /// Whether an edge with the given endpoints and relation exists.
+    pub fn has_edge(&self, from: NodeId, to: NodeId, relation: Relation) -> bool {
+        self.edges_between(from, to)
+            .iter()
+            .any(|edge_id| self.edges[edge_id].relation == relation)
+    }
+
+    /// All edges starting or ending at a node. Self-loops are included once.
+    pub fn incident_edges(&self, id: NodeId) -> Vec<EdgeId> {
+        let outgoing = self.node_outgoing_edges(id).unwrap_or_default();
+        let incoming = self.node_incoming_edges(id).unwrap_or_default();
+        let mut edges = outgoing.to_vec();
+        edges.extend(
+            incoming
+                .iter()
+                .filter(|&&edge_id| self.edges[&edge_id].from != id),
+        );
+        edges
+    }
+
+    /// The edges starting at a node, paired with their data.
+    pub fn outgoing(&self, id: NodeId) -> impl Iterator<Item = (EdgeId, &EdgeData)> + '_ {
+        self.node_outgoing_edges(id)
+            .unwrap_or_default()
+            .iter()
+            .map(|&edge_id| (edge_id, &self.edges[&edge_id]))
+    }
+
+    /// The edges ending at a node, paired with their data.
+    pub fn incoming(&self, id: NodeId) -> impl Iterator<Item = (EdgeId, &EdgeData)> + '_ {
+        self.node_incoming_edges(id)
+            .unwrap_or_default()
+            .iter()
+            .map(|&edge_id| (edge_id, &self.edges[&edge_id]))
+    }
+
     pub fn node_neighbors(&self, id: NodeId) -> Option<Vec<NodeId>> {
         self.node_outgoing_edges(id).map(|edges| {
             edges
@@ -81,6 +404,45 @@ impl Graph {
         })
     }
 
+    /// All nodes connected to a node by an edge in either direction, without duplicates.
+    pub fn neighbors_undirected(&self, id: NodeId) -> Vec<NodeId> {
+        let outgoing = self
+            .node_outgoing_edges(id)
+            .unwrap_or_default()
+            .iter()
+            .map(|edge_id| self.edges[edge_id].to);
+        let incoming = self
+            .node_incoming_edges(id)
+            .unwrap_or_default()
+            .iter()
+            .map(|edge_id| self.edges[edge_id].from);
+        let mut seen = HashSet::new();
+        outgoing
+            .chain(incoming)
+            .filter(|&neighbor| seen.insert(neighbor))
+            .collect()
+    }
+
+    /// `start` and all nodes at most `hops` edges away from it, ignoring edge direction.
+    pub fn neighbors_within(&self, start: NodeId, hops: usize) -> HashSet<NodeId> {
+        if !self.nodes.contains_key(&start) {
+            return HashSet::new();
+        }
+        let mut visited = HashSet::from([start]);
+        let mut frontier = vec![start];
+        for _ in 0..hops {
+            frontier = frontier
+                .into_iter()
+                .flat_map(|node_id| self.neighbors_undirected(node_id))
+                .filter(|&neighbor| visited.insert(neighbor))
+                .collect();
+            if frontier.is_empty() {
+                break;
+            }
+        }
+        visited
+    }
+
     pub fn node_children(&self, id: NodeId) -> Option<Vec<NodeId>> {
         self.node_outgoing_edges(id).map(|edges| {
             edges
@@ -91,6 +453,277 @@ impl Graph {
         })
     }
 
+    /// Visit the nodes reachable from `start` via outgoing edges in breadth-first order,
+    /// passing each node and its distance from `start` to the visitor.
+    pub fn visit_bfs(&self, start: NodeId, mut visitor: impl FnMut(NodeId, usize) -> Visit) {
+        if !self.nodes.contains_key(&start) {
+            return;
+        }
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from([(start, 0)]);
+        while let Some((node_id, depth)) = queue.pop_front() {
+            match visitor(node_id, depth) {
+                Visit::Continue => {}
+                Visit::Prune => continue,
+                Visit::Stop => return,
+            }
+            for neighbor in self.node_neighbors(node_id).unwrap_or_default() {
+                if visited.insert(neighbor) {
+                    queue.push_back((neighbor, depth + 1));
+                }
+            }
+        }
+    }
+
+    /// Visit the nodes reachable from `start` via outgoing edges in depth-first pre-order,
+    /// passing each node and its depth in the traversal to the visitor.
+    pub fn visit_dfs(&self, start: NodeId, mut visitor: impl FnMut(NodeId, usize) -> Visit) {
+        if !self.nodes.contains_key(&start) {
+            return;
+        }
+        let mut visited = HashSet::new();
+        let mut stack = vec![(start, 0)];
+        while let Some((node_id, depth)) = stack.pop() {
+            if !visited.insert(node_id) {
+                continue;
+            }
+            match visitor(node_id, depth) {
+                Visit::Continue => {}
+                Visit::Prune => continue,
+                Visit::Stop => return,
+            }
+            let neighbors = self.node_neighbors(node_id).unwrap_or_default();
+            stack.extend(
+                neighbors
+                    .into_iter()
+                    .rev()
+                    .map(|neighbor| (neighbor, depth + 1)),
+            );
+        }
+    }
+
+    /// All nodes reachable from a node via `IsParentOf` edges, excluding the node itself.
+    pub fn descendants(&self, id: NodeId) -> Vec<NodeId> {
+        let mut descendants = Vec::new();
+        let mut visited = HashSet::from([id]);
+        let mut stack = self.node_children(id).unwrap_or_default();
+        stack.reverse();
+        while let Some(node_id) = stack.pop() {
+            if !visited.insert(node_id) {
+                continue;
+            }
+            descendants.push(node_id);
+            let mut children = self.node_children(node_id).unwrap_or_default();
+            children.reverse();
+            stack.extend(children);
+        }
+        descendants
+    }
+
+    /// The number of `IsParentOf` descendants of a node.
+    pub fn subtree_size(&self, id: NodeId) -> usize {
+        self.descendants(id).len()
+    }
+
+    /// The subtree size of `root` and of each of its descendants, computed in a
+    /// single traversal. A node reachable via several parents counts only once,
+    /// towards the parent it is first reached from in breadth-first order.
+    pub fn subtree_sizes(&self, root: NodeId) -> HashMap<NodeId, usize> {
+        let mut sizes = HashMap::new();
+        if !self.nodes.contains_key(&root) {
+            return sizes;
+        }
+        let mut order = Vec::new();
+        let mut parents = HashMap::new();
+        let mut visited = HashSet::from([root]);
+        let mut queue = VecDeque::from([root]);
+        while let Some(node_id) = queue.pop_front() {
+            order.push(node_id);
+            for child in self.node_children(node_id).unwrap_or_default() {
+                if visited.insert(child) {
+                    parents.insert(child, node_id);
+                    queue.push_back(child);
+                }
+            }
+        }
+        // Children come after their parents in breadth-first order
+        for &node_id in order.iter().rev() {
+            let size = *sizes.entry(node_id).or_insert(0);
+            if let Some(&parent) = parents.get(&node_id) {
+                *sizes.entry(parent).or_insert(0) += size + 1;
+            }
+        }
+        sizes
+    }
+
+    /// `root` and its descendants in pre-order, each with its depth below `root`.
+    /// Children are visited in ascending ID order, following only `IsParentOf` edges.
+    pub fn subtree_preorder(&self, root: NodeId) -> Vec<(NodeId, usize)> {
+        if !self.nodes.contains_key(&root) {
+            return Vec::new();
+        }
+        let mut subtree = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![(root, 0)];
+        while let Some((node_id, depth)) = stack.pop() {
+            if !visited.insert(node_id) {
+                continue;
+            }
+            subtree.push((node_id, depth));
+            let mut children = self.node_children(node_id).unwrap_or_default();
+            children.sort_unstable_by(|a, b| b.cmp(a));
+            stack.extend(children.into_iter().map(|child| (child, depth + 1)));
+        }
+        subtree
+    }
+
+    /// All `Item` nodes contained in a file, including nested items.
+    pub fn items_in_file(&self, file_id: NodeId) -> Vec<NodeId> {
+        self.descendants(file_id)
+            .into_iter()
+            .filter(|&id| matches!(self.nodes[&id].contents, NodeContents::Item { .. }))
+            .collect()
+    }
+
+    /// The source of the first incoming `IsParentOf` edge of a node, if any.
+    pub fn node_parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node_incoming_edges(id)?
+            .iter()
+            .map(|&edge_id| &self.edges[&edge_id])
+            .find(|edge| edge.relation == Relation::IsParentOf)
+            .map(|edge| edge.from)
+    }
+
+    /// The position of a node among the children of its parent, in the order the
+    /// `IsParentOf` edges were added. Returns `None` for nodes without a parent.
+    pub fn sibling_index(&self, id: NodeId) -> Option<usize> {
+        let parent = self.node_parent(id)?;
+        self.node_children(parent)?
+            .into_iter()
+            .position(|child| child == id)
+    }
+
+    /// All ancestors of a node via `IsParentOf` edges, from its parent up to the root.
+    /// Stops early if the ancestors form a cycle.
+    pub fn ancestors(&self, id: NodeId) -> Vec<NodeId> {
+        let mut ancestors = Vec::new();
+        let mut visited = HashSet::from([id]);
+        let mut current = id;
+        while let Some(parent) = self.node_parent(current) {
+            if !visited.insert(parent) {
+                break;
+            }
+            ancestors.push(parent);
+            current = parent;
+        }
+        ancestors
+    }
+
+    /// The display names from the root down to a node, joined by `sep`.
+    pub fn display_path(&self, id: NodeId, sep: &str) -> Option<String> {
+        let node = self.nodes.get(&id)?;
+        let mut names: Vec<_> = self
+            .ancestors(id)
+            .into_iter()
+            .map(|ancestor| self.nodes[&ancestor].contents.display_name())
+            .collect();
+        names.reverse();
+        names.push(node.contents.display_name());
+        Some(names.join(sep))
+    }
+
+    /// The deepest node that is `a`, `b` or an ancestor of both, following `IsParentOf` edges.
+    /// Returns `None` if the nodes are in different trees.
+    pub fn lca(&self, a: NodeId, b: NodeId) -> Option<NodeId> {
+        if !self.nodes.contains_key(&a) || !self.nodes.contains_key(&b) {
+            return None;
+        }
+        let mut a_chain = HashSet::from([a]);
+        a_chain.extend(self.ancestors(a));
+        std::iter::once(b)
+            .chain(self.ancestors(b))
+            .find(|node_id| a_chain.contains(node_id))
+    }
+
+    /// The closest `File` ancestor of a node.
+    pub fn containing_file(&self, id: NodeId) -> Option<NodeId> {
+        self.ancestors(id)
+            .into_iter()
+            .find(|ancestor| matches!(self.nodes[ancestor].contents, NodeContents::File { .. }))
+    }
+
+    /// A key identifying a node independently of its ID, stable across analysis runs.
+    /// Folders and files are keyed by path, items by their enclosing file and the
+    /// names of their enclosing items. Returns `None` if the ancestors form a cycle.
+    pub fn stable_key(&self, id: NodeId) -> Option<String> {
+        let mut segments = Vec::new();
+        let mut visited = HashSet::new();
+        let mut current = id;
+        let prefix = loop {
+            if !visited.insert(current) {
+                return None;
+            }
+            match &self.nodes.get(&current)?.contents {
+                NodeContents::Workspace { display_name } => {
+                    break format!("workspace:{}", display_name)
+                }
+                NodeContents::Folder { path, .. } => break format!("folder:{}", path.display()),
+                NodeContents::File { path, .. } => break format!("file:{}", path.display()),
+                NodeContents::Crate { manifest_path, .. } => {
+                    break format!("crate:{}", manifest_path.display())
+                }
+                NodeContents::Item { display_name, .. } => {
+                    let parent = self.node_parent(current);
+                    // Disambiguate items sharing a name with earlier siblings
+                    let ordinal = parent
+                        .and_then(|parent| self.node_children(parent))
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|&sibling| {
+                            sibling < current
+                                && self.nodes[&sibling].contents.display_name() == display_name
+                        })
+                        .count();
+                    if ordinal == 0 {
+                        segments.push(display_name.clone());
+                    } else {
+                        segments.push(format!("{}#{}", display_name, ordinal));
+                    }
+                    match parent {
+                        Some(parent) => current = parent,
+                        None => break "item".to_owned(),
+                    }
+                }
+            }
+        };
+        segments.reverse();
+        Some(
+            std::iter::once(prefix)
+                .chain(segments)
+                .collect::<Vec<_>>()
+                .join("::"),
+        )
+    }
+
+    /// The number of `IsParentOf` edges between a node and its root (root = 0).
+    /// Returns `None` if the node does not exist or its ancestors form a cycle.
+    pub fn depth(&self, id: NodeId) -> Option<usize> {
+        if !self.nodes.contains_key(&id) {
+            return None;
+        }
+        let mut visited = HashSet::from([id]);
+        let mut depth = 0;
+        let mut current = id;
+        while let Some(parent) = self.node_parent(current) {
+            if !visited.insert(parent) {
+                return None;
+            }
+            depth += 1;
+            current = parent;
+        }
+        Some(depth)
+    }
+
     pub fn node_data(&self, id: NodeId) -> Option<&NodeData> {
         self.nodes.get(&id)
     }
@@ -107,6 +740,69 @@ impl Graph {
         self.edges.get_mut(&id)
     }
 
+    /// Update the display name of a node. Returns whether the node exists.
+    pub fn rename_node(&mut self, id: NodeId, new_name: String) -> bool {
+        let Some(node) = self.nodes.get_mut(&id) else {
+            return false;
+        };
+        *node.contents.display_name_mut() = new_name;
+        true
+    }
+
+    /// Replace the contents of a node. Returns whether the node exists.
+    pub fn set_node_contents(&mut self, id: NodeId, contents: NodeContents) -> bool {
+        let Some(node) = self.nodes.get_mut(&id) else {
+            return false;
+        };
+        node.contents = contents;
+        true
+    }
+
+    /// The folder or file node with the given path.
+    ///
+    /// This scans all nodes, as paths can change through [`Graph::node_mut`] and are
+    /// therefore not indexed. Use [`Graph::path_index`] to look up many paths.
+    pub fn node_by_path(&self, path: &Path) -> Option<NodeId> {
+        self.find_first(|node| node.contents.file_path() == Some(path))
+    }
+
+    /// The folder and file nodes by path, valid until a node is added, removed or modified.
+    pub fn path_index(&self) -> HashMap<PathBuf, NodeId> {
+        self.nodes
+            .iter()
+            .filter_map(|(&id, node)| Some((node.contents.file_path()?.to_owned(), id)))
+            .collect()
+    }
+
+    /// All nodes matching the predicate, in ascending ID order.
+    pub fn find(&self, pred: impl Fn(&NodeData) -> bool) -> Vec<NodeId> {
+        let mut found: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| pred(node))
+            .map(|(&id, _)| id)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    /// The node with the lowest ID matching the predicate.
+    pub fn find_first(&self, pred: impl Fn(&NodeData) -> bool) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| pred(node))
+            .map(|(&id, _)| id)
+            .min()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
     pub fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
         self.nodes.keys().copied()
     }
@@ -115,6 +811,38 @@ impl Graph {
         self.edges.keys().copied()
     }
 
+    pub fn edges_of_relation(&self, relation: Relation) -> impl Iterator<Item = EdgeId> + '_ {
+        self.edges
+            .iter()
+            .filter(move |(_, edge)| edge.relation == relation)
+            .map(|(&id, _)| id)
+    }
+
+    /// All `File` nodes, in no particular order.
+    pub fn files(&self) -> impl Iterator<Item = (NodeId, &NodeData)> + '_ {
+        self.nodes_matching(|contents| matches!(contents, NodeContents::File { .. }))
+    }
+
+    /// All `Folder` nodes, in no particular order.
+    pub fn folders(&self) -> impl Iterator<Item = (NodeId, &NodeData)> + '_ {
+        self.nodes_matching(|contents| matches!(contents, NodeContents::Folder { .. }))
+    }
+
+    /// All `Item` nodes, in no particular order.
+    pub fn items(&self) -> impl Iterator<Item = (NodeId, &NodeData)> + '_ {
+        self.nodes_matching(|contents| matches!(contents, NodeContents::Item { .. }))
+    }
+
+    fn nodes_matching(
+        &self,
+        pred: fn(&NodeContents) -> bool,
+    ) -> impl Iterator<Item = (NodeId, &NodeData)> + '_ {
+        self.nodes
+            .iter()
+            .filter(move |(_, node)| pred(&node.contents))
+            .map(|(&id, node)| (id, node))
+    }
+
     fn fresh_node_id(&mut self) -> NodeId {
         let id = self.last_node_id;
         self.last_node_id += 1;
@@ -128,13 +856,64 @@ impl Graph {
     }
 }
 
+/// How a traversal proceeds after a visitor has seen a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+    /// Continue with the node's neighbors.
+    Continue,
+    /// Skip the node's neighbors but continue the traversal elsewhere.
+    Prune,
+    /// Stop the traversal.
+    Stop,
+}
+
+/// Mapping from old to new IDs, as returned by [`Graph::compact`].
+#[derive(Debug, Default)]
+pub struct IdMapping {
+    pub nodes: HashMap<NodeId, NodeId>,
+    pub edges: HashMap<EdgeId, EdgeId>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NodeData {
     pub contents: NodeContents,
+    /// The most severe diagnostic reported for this node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_severity: Option<Severity>,
+    /// Arbitrary data attached by consumers, such as tags or coverage.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+impl NodeData {
+    pub fn new(contents: NodeContents) -> Self {
+        Self {
+            contents,
+            max_severity: None,
+            metadata: serde_json::Map::new(),
+        }
+    }
+
+    pub fn get_metadata(&self, key: &str) -> Option<&serde_json::Value> {
+        self.metadata.get(key)
+    }
+
+    /// Set a metadata entry, returning the previous value.
+    pub fn set_metadata(
+        &mut self,
+        key: impl Into<String>,
+        value: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        self.metadata.insert(key.into(), value)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum NodeContents {
+    /// A synthetic root above the analyzed folders.
+    Workspace {
+        display_name: String,
+    },
     Folder {
         display_name: String,
         path: PathBuf,
@@ -143,12 +922,131 @@ pub enum NodeContents {
         display_name: String,
         path: PathBuf,
     },
+    Crate {
+        display_name: String,
+        manifest_path: PathBuf,
+    },
     Item {
         display_name: String,
         moniker: Option<String>,
+        /// The source range of the item within its file.
+        #[serde(default)]
+        range: Option<Range>,
+        /// The range of the item's name, within `range`.
+        #[serde(default)]
+        selection_range: Option<Range>,
+        /// Documentation and signature of the item, as shown on hover.
+        #[serde(default)]
+        doc: Option<String>,
+        /// The kind of symbol the item represents.
+        #[serde(default)]
+        kind: Option<SymbolKind>,
     },
 }
 
+/// The kind of a symbol, mirroring the LSP symbol kinds.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    File,
+    Module,
+    Namespace,
+    Package,
+    Class,
+    Method,
+    Property,
+    Field,
+    Constructor,
+    Enum,
+    Interface,
+    Function,
+    Variable,
+    Constant,
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Key,
+    Null,
+    EnumMember,
+    Struct,
+    Event,
+    Operator,
+    TypeParameter,
+}
+
+impl NodeContents {
+    pub fn display_name(&self) -> &str {
+        match self {
+            NodeContents::Workspace { display_name }
+            | NodeContents::Folder { display_name, .. }
+            | NodeContents::File { display_name, .. }
+            | NodeContents::Crate { display_name, .. }
+            | NodeContents::Item { display_name, .. } => display_name,
+        }
+    }
+
+    /// The path of a folder or file, or the manifest path of a crate.
+    fn path_mut(&mut self) -> Option<&mut PathBuf> {
+        match self {
+            NodeContents::Folder { path, .. } | NodeContents::File { path, .. } => Some(path),
+            NodeContents::Crate { manifest_path, .. } => Some(manifest_path),
+            NodeContents::Workspace { .. } | NodeContents::Item { .. } => None,
+        }
+    }
+
+    pub fn display_name_mut(&mut self) -> &mut String {
+        match self {
+            NodeContents::Workspace { display_name }
+            | NodeContents::Folder { display_name, .. }
+            | NodeContents::File { display_name, .. }
+            | NodeContents::Crate { display_name, .. }
+            | NodeContents::Item { display_name, .. } => display_name,
+        }
+    }
+
+    /// The path of a folder or file.
+    fn file_path(&self) -> Option<&Path> {
+        match self {
+            NodeContents::Folder { path, .. } | NodeContents::File { path, .. } => Some(path),
+            NodeContents::Workspace { .. }
+            | NodeContents::Crate { .. }
+            | NodeContents::Item { .. } => None,
+        }
+    }
+}
+
+/// A zero-based position in a text document.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// A range in a text document, with an exclusive end.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    /// Whether the ranges share at least one position. Empty ranges overlap
+    /// ranges that strictly contain them or start at the same position.
+    pub fn overlaps(&self, other: &Range) -> bool {
+        (self.start < other.end && other.start < self.end) || self.start == other.start
+    }
+}
+
+/// Diagnostic severities, ordered from least to most severe.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Hint,
+    Information,
+    Warning,
+    Error,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct EdgeData {
     pub from: NodeId,
@@ -156,8 +1054,721 @@ pub struct EdgeData {
     pub relation: Relation,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Relation {
     /// <from> is parent of <to>
     IsParentOf,
+    /// <from> is a module declaration whose contents are in file <to>
+    DeclaresModule,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_folder(graph: &mut Graph, name: &str) -> NodeId {
+        graph.add_node(NodeData::new(NodeContents::Folder {
+            display_name: name.to_owned(),
+            path: PathBuf::from(name),
+        }))
+    }
+
+    fn add_item(graph: &mut Graph, name: &str) -> NodeId {
+        graph.add_node(NodeData::new(NodeContents::Item {
+            display_name: name.to_owned(),
+            moniker: None,
+            range: None,
+            selection_range: None,
+            doc: None,
+            kind: None,
+        }))
+    }
+
+    fn add_file(graph: &mut Graph, name: &str) -> NodeId {
+        graph.add_node(NodeData::new(NodeContents::File {
+            display_name: name.to_owned(),
+            path: PathBuf::from(name),
+        }))
+    }
+
+    fn add_parent_edge(graph: &mut Graph, from: NodeId, to: NodeId) -> EdgeId {
+        graph.add_edge(EdgeData {
+            from,
+            to,
+            relation: Relation::IsParentOf,
+        })
+    }
+
+    #[test]
+    fn rename_node_updates_the_display_name() {
+        let mut graph = Graph::default();
+        let folder = add_folder(&mut graph, "src");
+
+        assert!(graph.rename_node(folder, "lib".to_owned()));
+        assert_eq!(graph.node(folder).unwrap().contents.display_name(), "lib");
+        assert!(!graph.rename_node(folder + 1, "missing".to_owned()));
+    }
+
+    #[test]
+    fn set_node_contents_replaces_the_contents() {
+        let mut graph = Graph::default();
+        let node = add_item(&mut graph, "main");
+
+        let contents = NodeContents::File {
+            display_name: "main.rs".to_owned(),
+            path: PathBuf::from("src/main.rs"),
+        };
+        assert!(graph.set_node_contents(node, contents));
+        assert!(matches!(
+            graph.node(node).unwrap().contents,
+            NodeContents::File { .. }
+        ));
+        assert_eq!(graph.node(node).unwrap().contents.display_name(), "main.rs");
+    }
+
+    #[test]
+    fn depth_counts_parent_edges_to_the_root() {
+        let mut graph = Graph::default();
+        let root = add_folder(&mut graph, "root");
+        let src = add_folder(&mut graph, "src");
+        let item = add_item(&mut graph, "main");
+        add_parent_edge(&mut graph, root, src);
+        add_parent_edge(&mut graph, src, item);
+
+        assert_eq!(graph.node_parent(item), Some(src));
+        assert_eq!(graph.depth(root), Some(0));
+        assert_eq!(graph.depth(src), Some(1));
+        assert_eq!(graph.depth(item), Some(2));
+        assert_eq!(graph.depth(item + 1), None);
+    }
+
+    #[test]
+    fn depth_is_none_for_a_parent_cycle() {
+        let mut graph = Graph::default();
+        let a = add_folder(&mut graph, "a");
+        let b = add_folder(&mut graph, "b");
+        add_parent_edge(&mut graph, a, b);
+        add_parent_edge(&mut graph, b, a);
+
+        assert_eq!(graph.depth(a), None);
+    }
+
+    #[test]
+    fn find_returns_matching_nodes_in_id_order() {
+        let mut graph = Graph::default();
+        let src = add_folder(&mut graph, "src");
+        let main = add_item(&mut graph, "main");
+        let tests = add_folder(&mut graph, "tests");
+        let is_folder = |node: &NodeData| matches!(node.contents, NodeContents::Folder { .. });
+
+        assert_eq!(graph.find(is_folder), [src, tests]);
+        assert_eq!(graph.find_first(is_folder), Some(src));
+        assert_eq!(
+            graph.find_first(|node| node.contents.display_name() == "main"),
+            Some(main)
+        );
+        assert_eq!(graph.find_first(|_| false), None);
+    }
+
+    #[test]
+    fn items_in_file_include_nested_items() {
+        let mut graph = Graph::default();
+        let src = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "lib.rs");
+        let module = add_item(&mut graph, "module");
+        let function = add_item(&mut graph, "function");
+        let other = add_file(&mut graph, "main.rs");
+        let main = add_item(&mut graph, "main");
+        add_parent_edge(&mut graph, src, file);
+        add_parent_edge(&mut graph, file, module);
+        add_parent_edge(&mut graph, module, function);
+        add_parent_edge(&mut graph, src, other);
+        add_parent_edge(&mut graph, other, main);
+
+        assert_eq!(graph.items_in_file(file), [module, function]);
+        assert_eq!(
+            graph.descendants(src),
+            [file, module, function, other, main]
+        );
+        assert!(graph.descendants(main).is_empty());
+    }
+
+    #[test]
+    fn remove_node_removes_incident_edges() {
+        let mut graph = Graph::default();
+        let src = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "lib.rs");
+        let item = add_item(&mut graph, "main");
+        add_parent_edge(&mut graph, src, file);
+        add_parent_edge(&mut graph, file, item);
+
+        assert!(graph.remove_node(file).is_some());
+        assert!(graph.remove_node(file).is_none());
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.edge_count(), 0);
+        assert_eq!(graph.node_children(src), Some(vec![]));
+        assert_eq!(graph.node_parent(item), None);
+    }
+
+    #[test]
+    fn compact_makes_ids_contiguous() {
+        let mut graph = Graph::default();
+        let removed = add_folder(&mut graph, "removed");
+        let src = add_folder(&mut graph, "src");
+        let item = add_item(&mut graph, "main");
+        add_parent_edge(&mut graph, removed, src);
+        let edge = add_parent_edge(&mut graph, src, item);
+        graph.remove_node(removed);
+
+        let mapping = graph.compact();
+        assert_eq!(mapping.nodes[&src], 0);
+        assert_eq!(mapping.nodes[&item], 1);
+        assert_eq!(mapping.edges[&edge], 0);
+        assert_eq!(graph.node(0usize).unwrap().contents.display_name(), "src");
+        assert_eq!(graph.node_children(0), Some(vec![1]));
+        assert_eq!(add_item(&mut graph, "new"), 2);
+    }
+
+    #[test]
+    fn compact_drops_orphan_edges() {
+        let mut graph = Graph::default();
+        let src = add_folder(&mut graph, "src");
+        add_parent_edge(&mut graph, src, src + 1);
+
+        let mapping = graph.compact();
+        assert_eq!(graph.edge_count(), 0);
+        assert!(mapping.edges.is_empty());
+        assert_eq!(graph.node_children(0), Some(vec![]));
+    }
+
+    #[test]
+    fn range_overlaps() {
+        let range = |start: (u32, u32), end: (u32, u32)| Range {
+            start: Position {
+                line: start.0,
+                character: start.1,
+            },
+            end: Position {
+                line: end.0,
+                character: end.1,
+            },
+        };
+        let item = range((1, 0), (5, 0));
+
+        assert!(item.overlaps(&range((4, 2), (8, 0))));
+        assert!(item.overlaps(&range((2, 0), (2, 0))));
+        assert!(item.overlaps(&range((1, 0), (1, 0))));
+        assert!(!item.overlaps(&range((5, 0), (6, 0))));
+        assert!(!item.overlaps(&range((5, 0), (5, 0))));
+    }
+
+    #[test]
+    fn containing_file_is_the_closest_file_ancestor() {
+        let mut graph = Graph::default();
+        let src = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "lib.rs");
+        let module = add_item(&mut graph, "module");
+        let function = add_item(&mut graph, "function");
+        add_parent_edge(&mut graph, src, file);
+        add_parent_edge(&mut graph, file, module);
+        add_parent_edge(&mut graph, module, function);
+
+        assert_eq!(graph.ancestors(function), [module, file, src]);
+        assert_eq!(graph.containing_file(function), Some(file));
+        assert_eq!(graph.containing_file(file), None);
+        assert!(graph.ancestors(src).is_empty());
+    }
+
+    #[test]
+    fn neighbors_undirected_follows_edges_both_ways_once() {
+        let mut graph = Graph::default();
+        let src = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "lib.rs");
+        let item = add_item(&mut graph, "main");
+        add_parent_edge(&mut graph, src, file);
+        add_parent_edge(&mut graph, file, item);
+        add_parent_edge(&mut graph, item, file);
+
+        assert_eq!(graph.neighbors_undirected(file), [item, src]);
+        assert_eq!(graph.neighbors_undirected(src), [file]);
+        assert!(graph.neighbors_undirected(item + 1).is_empty());
+    }
+
+    #[test]
+    fn stable_key_uses_paths_and_item_names() {
+        let mut graph = Graph::default();
+        let file = add_file(&mut graph, "lib.rs");
+        let module = add_item(&mut graph, "tests");
+        let first = add_item(&mut graph, "new");
+        let second = add_item(&mut graph, "new");
+        add_parent_edge(&mut graph, file, module);
+        add_parent_edge(&mut graph, module, first);
+        add_parent_edge(&mut graph, module, second);
+
+        assert_eq!(graph.stable_key(file).unwrap(), "file:lib.rs");
+        assert_eq!(graph.stable_key(first).unwrap(), "file:lib.rs::tests::new");
+        assert_eq!(
+            graph.stable_key(second).unwrap(),
+            "file:lib.rs::tests::new#1"
+        );
+        assert_eq!(graph.stable_key(second + 1), None);
+    }
+
+    #[test]
+    fn edges_of_relation_lists_matching_edges() {
+        let mut graph = Graph::default();
+        let folder = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "main.rs");
+        let item = add_item(&mut graph, "main");
+        let to_file = add_parent_edge(&mut graph, folder, file);
+        let to_item = add_parent_edge(&mut graph, file, item);
+
+        let mut edges: Vec<_> = graph.edges_of_relation(Relation::IsParentOf).collect();
+        edges.sort();
+        assert_eq!(edges, vec![to_file, to_item]);
+
+        graph.remove_node(item);
+        let edges: Vec<_> = graph.edges_of_relation(Relation::IsParentOf).collect();
+        assert_eq!(edges, vec![to_file]);
+    }
+
+    #[test]
+    fn path_index_matches_node_by_path() {
+        let mut graph = Graph::default();
+        let folder = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "main.rs");
+        add_item(&mut graph, "main");
+
+        let index = graph.path_index();
+        assert_eq!(index.len(), 2);
+        for (path, node) in [("src", folder), ("main.rs", file)] {
+            assert_eq!(index[Path::new(path)], node);
+            assert_eq!(graph.node_by_path(Path::new(path)), Some(node));
+        }
+        assert_eq!(graph.node_by_path(Path::new("main")), None);
+    }
+
+    #[test]
+    fn retain_reachable_follows_outgoing_edges() {
+        let mut graph = Graph::default();
+        let root = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "main.rs");
+        let item = add_item(&mut graph, "main");
+        let other_root = add_folder(&mut graph, "tests");
+        add_parent_edge(&mut graph, root, file);
+        add_parent_edge(&mut graph, file, item);
+        add_parent_edge(&mut graph, other_root, file);
+
+        graph.retain_reachable(&[root, item + 100]);
+
+        let mut nodes: Vec<_> = graph.nodes().collect();
+        nodes.sort();
+        assert_eq!(nodes, vec![root, file, item]);
+        assert_eq!(graph.edges().count(), 2);
+    }
+
+    /// A folder with two files, the first of which contains an item.
+    fn small_tree(graph: &mut Graph) -> [NodeId; 4] {
+        let root = add_folder(graph, "src");
+        let first = add_file(graph, "a.rs");
+        let second = add_file(graph, "b.rs");
+        let item = add_item(graph, "main");
+        add_parent_edge(graph, root, first);
+        add_parent_edge(graph, root, second);
+        add_parent_edge(graph, first, item);
+        [root, first, second, item]
+    }
+
+    #[test]
+    fn visit_bfs_visits_by_distance_and_prunes() {
+        let mut graph = Graph::default();
+        let [root, first, second, item] = small_tree(&mut graph);
+
+        let mut visited = Vec::new();
+        graph.visit_bfs(root, |node_id, depth| {
+            visited.push((node_id, depth));
+            Visit::Continue
+        });
+        assert_eq!(visited, vec![(root, 0), (first, 1), (second, 1), (item, 2)]);
+
+        let mut visited = Vec::new();
+        graph.visit_bfs(root, |node_id, _| {
+            visited.push(node_id);
+            if node_id == first {
+                Visit::Prune
+            } else {
+                Visit::Continue
+            }
+        });
+        assert_eq!(visited, vec![root, first, second]);
+    }
+
+    #[test]
+    fn visit_dfs_visits_in_pre_order_and_stops() {
+        let mut graph = Graph::default();
+        let [root, first, second, item] = small_tree(&mut graph);
+
+        let mut visited = Vec::new();
+        graph.visit_dfs(root, |node_id, depth| {
+            visited.push((node_id, depth));
+            Visit::Continue
+        });
+        assert_eq!(visited, vec![(root, 0), (first, 1), (item, 2), (second, 1)]);
+
+        let mut visited = Vec::new();
+        graph.visit_dfs(root, |node_id, _| {
+            visited.push(node_id);
+            if node_id == item {
+                Visit::Stop
+            } else {
+                Visit::Continue
+            }
+        });
+        assert_eq!(visited, vec![root, first, item]);
+    }
+
+    #[test]
+    fn display_path_joins_names_from_the_root() {
+        let mut graph = Graph::default();
+        let [root, first, _, item] = small_tree(&mut graph);
+
+        assert_eq!(
+            graph.display_path(item, " > ").unwrap(),
+            "src > a.rs > main"
+        );
+        assert_eq!(graph.display_path(first, "/").unwrap(), "src/a.rs");
+        assert_eq!(graph.display_path(root, "/").unwrap(), "src");
+        assert_eq!(graph.display_path(item + 1, "/"), None);
+    }
+
+    #[test]
+    fn replace_subtree_swaps_all_descendants() {
+        let mut graph = Graph::default();
+        let [root, first, second, item] = small_tree(&mut graph);
+
+        let children = ["lib.rs", "mod.rs"].map(|name| {
+            NodeData::new(NodeContents::File {
+                display_name: name.to_owned(),
+                path: PathBuf::from(name),
+            })
+        });
+        let new_children = graph.replace_subtree(root, children.into_iter());
+
+        assert_eq!(new_children.len(), 2);
+        for removed in [first, second, item] {
+            assert!(graph.node(removed).is_none());
+        }
+        assert_eq!(graph.node_children(root), Some(new_children));
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.edges().count(), 2);
+    }
+
+    #[test]
+    fn add_edge_deduped_reuses_an_identical_edge() {
+        let mut graph = Graph::default();
+        let folder = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "main.rs");
+        let edge = add_parent_edge(&mut graph, folder, file);
+
+        let duplicate = graph.add_edge_deduped(EdgeData {
+            from: folder,
+            to: file,
+            relation: Relation::IsParentOf,
+        });
+        assert_eq!(duplicate, edge);
+        assert_eq!(graph.edges().count(), 1);
+
+        let reversed = graph.add_edge_deduped(EdgeData {
+            from: file,
+            to: folder,
+            relation: Relation::IsParentOf,
+        });
+        assert_ne!(reversed, edge);
+        assert_eq!(graph.edges_between(folder, file), vec![edge]);
+        assert!(graph.has_edge(file, folder, Relation::IsParentOf));
+    }
+
+    #[test]
+    fn metadata_is_set_and_serialized_only_when_present() {
+        let mut graph = Graph::default();
+        let item = add_item(&mut graph, "main");
+        let node = graph.node(item).unwrap();
+        assert!(serde_json::to_value(node)
+            .unwrap()
+            .get("metadata")
+            .is_none());
+
+        let node = graph.node_mut(item).unwrap();
+        assert_eq!(node.set_metadata("coverage", serde_json::json!(0.5)), None);
+        assert_eq!(
+            node.set_metadata("coverage", serde_json::json!(0.75)),
+            Some(serde_json::json!(0.5))
+        );
+        assert_eq!(
+            node.get_metadata("coverage"),
+            Some(&serde_json::json!(0.75))
+        );
+
+        let json = serde_json::to_value(&*node).unwrap();
+        assert_eq!(json["metadata"]["coverage"], 0.75);
+        let decoded: NodeData = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.metadata, node.metadata);
+    }
+
+    #[test]
+    fn subtree_preorder_lists_children_by_id() {
+        let mut graph = Graph::default();
+        let [root, first, second, item] = small_tree(&mut graph);
+        let other = add_item(&mut graph, "other");
+        graph.add_edge(EdgeData {
+            from: first,
+            to: other,
+            relation: Relation::DeclaresModule,
+        });
+
+        assert_eq!(
+            graph.subtree_preorder(root),
+            vec![(root, 0), (first, 1), (item, 2), (second, 1)]
+        );
+        assert_eq!(graph.subtree_preorder(first), vec![(first, 0), (item, 1)]);
+        assert!(graph.subtree_preorder(other + 1).is_empty());
+    }
+
+    #[test]
+    fn incident_edges_lists_self_loops_once() {
+        let mut graph = Graph::default();
+        let [root, first, _, item] = small_tree(&mut graph);
+        let self_loop = add_parent_edge(&mut graph, first, first);
+
+        let mut incident = graph.incident_edges(first);
+        incident.sort_unstable();
+        let mut expected = [
+            graph.edges_between(root, first)[0],
+            graph.edges_between(first, item)[0],
+            self_loop,
+        ];
+        expected.sort_unstable();
+        assert_eq!(incident, expected);
+        assert!(graph.incident_edges(item + 100).is_empty());
+    }
+
+    #[test]
+    fn lca_finds_the_deepest_shared_ancestor() {
+        let mut graph = Graph::default();
+        let [root, first, second, item] = small_tree(&mut graph);
+        let other_root = add_folder(&mut graph, "other");
+
+        assert_eq!(graph.lca(item, second), Some(root));
+        assert_eq!(graph.lca(item, first), Some(first));
+        assert_eq!(graph.lca(item, item), Some(item));
+        assert_eq!(graph.lca(item, other_root), None);
+        assert_eq!(graph.lca(item, other_root + 1), None);
+    }
+
+    #[test]
+    fn transitive_reduction_removes_shortcuts_of_the_relation() {
+        let mut graph = Graph::default();
+        let [root, _, second, item] = small_tree(&mut graph);
+        let shortcut = add_parent_edge(&mut graph, root, item);
+        let declaration = graph.add_edge(EdgeData {
+            from: root,
+            to: second,
+            relation: Relation::DeclaresModule,
+        });
+        let mut tree = graph
+            .edges_of_relation(Relation::IsParentOf)
+            .collect::<Vec<_>>();
+        tree.retain(|&edge_id| edge_id != shortcut);
+        tree.sort_unstable();
+
+        graph.transitive_reduction(Relation::IsParentOf);
+        let mut remaining = graph
+            .edges_of_relation(Relation::IsParentOf)
+            .collect::<Vec<_>>();
+        remaining.sort_unstable();
+        assert_eq!(remaining, tree);
+        assert!(graph.edge(declaration).is_some());
+        assert_eq!(graph.lca(item, second), Some(root));
+    }
+
+    #[test]
+    fn neighbors_within_stops_at_the_hop_radius() {
+        let mut graph = Graph::default();
+        let line: Vec<_> = (0..5)
+            .map(|i| add_item(&mut graph, &format!("item{i}")))
+            .collect();
+        for pair in line.windows(2) {
+            add_parent_edge(&mut graph, pair[0], pair[1]);
+        }
+
+        assert_eq!(
+            graph.neighbors_within(line[2], 1),
+            HashSet::from([line[1], line[2], line[3]])
+        );
+        assert_eq!(
+            graph.neighbors_within(line[0], 2),
+            HashSet::from([line[0], line[1], line[2]])
+        );
+        assert_eq!(graph.neighbors_within(line[4], 0), HashSet::from([line[4]]));
+        assert_eq!(graph.neighbors_within(line[0], 10).len(), 5);
+        assert!(graph.neighbors_within(line[4] + 1, 1).is_empty());
+    }
+
+    #[test]
+    fn outgoing_and_incoming_pair_edges_with_their_data() {
+        let mut graph = Graph::default();
+        let [root, first, second, _] = small_tree(&mut graph);
+
+        let mut outgoing: Vec<_> = graph
+            .outgoing(root)
+            .map(|(edge_id, edge)| (edge_id, edge.to))
+            .collect();
+        outgoing.sort_unstable();
+        let mut expected: Vec<_> = graph
+            .node_outgoing_edges(root)
+            .unwrap()
+            .iter()
+            .map(|&edge_id| (edge_id, graph.edge(edge_id).unwrap().to))
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(outgoing, expected);
+        assert_eq!(
+            outgoing.iter().map(|&(_, to)| to).collect::<Vec<_>>(),
+            [first, second]
+        );
+
+        let incoming: Vec<_> = graph.incoming(first).collect();
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].1.from, root);
+        assert_eq!(incoming[0].1.relation, Relation::IsParentOf);
+        assert_eq!(graph.outgoing(second + 100).count(), 0);
+    }
+
+    #[test]
+    fn variant_iterators_yield_only_their_variant() {
+        let mut graph = Graph::default();
+        let folder = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "main.rs");
+        let item = add_item(&mut graph, "main");
+        add_parent_edge(&mut graph, folder, file);
+        add_parent_edge(&mut graph, file, item);
+
+        let ids = |nodes: Vec<(NodeId, &NodeData)>| -> Vec<NodeId> {
+            nodes.into_iter().map(|(id, _)| id).collect()
+        };
+        assert_eq!(ids(graph.folders().collect()), [folder]);
+        assert_eq!(ids(graph.files().collect()), [file]);
+        assert_eq!(ids(graph.items().collect()), [item]);
+    }
+
+    #[test]
+    fn contains_checks_node_and_edge_ids() {
+        let mut graph = Graph::default();
+        let folder = add_folder(&mut graph, "src");
+        let file = add_file(&mut graph, "main.rs");
+        let edge = add_parent_edge(&mut graph, folder, file);
+
+        assert!(graph.contains_node(folder));
+        assert!(graph.contains_edge(edge));
+        assert!(!graph.contains_node(file + 1));
+        assert!(!graph.contains_edge(edge + 1));
+
+        graph.remove_node(file);
+        assert!(!graph.contains_node(file));
+        assert!(!graph.contains_edge(edge));
+    }
+
+    #[test]
+    fn subtree_sizes_count_descendants() {
+        let mut graph = Graph::default();
+        let [root, first, second, item] = small_tree(&mut graph);
+
+        assert_eq!(graph.subtree_size(root), 3);
+        assert_eq!(graph.subtree_size(first), 1);
+        assert_eq!(graph.subtree_size(item), 0);
+
+        let sizes = graph.subtree_sizes(root);
+        assert_eq!(
+            sizes,
+            HashMap::from([(root, 3), (first, 1), (second, 0), (item, 0)])
+        );
+        assert_eq!(
+            graph.subtree_sizes(first),
+            HashMap::from([(first, 1), (item, 0)])
+        );
+        assert!(graph.subtree_sizes(item + 1).is_empty());
+    }
+
+    #[test]
+    fn retain_relations_drops_edges_of_other_relations() {
+        let mut graph = Graph::default();
+        let [root, first, _, item] = small_tree(&mut graph);
+        let declares = graph.add_edge(EdgeData {
+            from: item,
+            to: first,
+            relation: Relation::DeclaresModule,
+        });
+
+        graph.retain_relations(&HashSet::from([Relation::IsParentOf]));
+        assert_eq!(graph.node_count(), 4);
+        assert!(!graph.contains_edge(declares));
+        assert_eq!(graph.edges_of_relation(Relation::DeclaresModule).count(), 0);
+        assert_eq!(graph.edges_of_relation(Relation::IsParentOf).count(), 3);
+        assert_eq!(graph.incoming(first).count(), 1);
+        assert_eq!(graph.outgoing(item).count(), 0);
+        assert_eq!(graph.subtree_size(root), 3);
+    }
+
+    #[test]
+    fn relative_paths_are_serialized_and_resolved_on_load() {
+        let root = Path::new("/work/project");
+        let mut graph = Graph::default();
+        let file = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "lib.rs".to_owned(),
+            path: root.join("src/lib.rs"),
+        }));
+        let outside = graph.add_node(NodeData::new(NodeContents::Folder {
+            display_name: "registry".to_owned(),
+            path: PathBuf::from("/home/user/.cargo/registry"),
+        }));
+
+        graph.make_paths_relative(root);
+        let json = serde_json::to_value(&graph).unwrap();
+        assert_eq!(json["path_root"], "/work/project");
+        let file_path = |graph: &Graph| {
+            graph
+                .node(file)
+                .unwrap()
+                .contents
+                .file_path()
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!(file_path(&graph), Path::new("src/lib.rs"));
+
+        let mut loaded: Graph = serde_json::from_value(json).unwrap();
+        let path_root = loaded.path_root().unwrap().to_owned();
+        loaded.make_paths_absolute(&path_root);
+        assert_eq!(file_path(&loaded), root.join("src/lib.rs"));
+        assert_eq!(
+            loaded.node(outside).unwrap().contents.file_path(),
+            Some(Path::new("/home/user/.cargo/registry"))
+        );
+        assert_eq!(loaded.path_root(), None);
+    }
+
+    #[test]
+    fn sibling_index_follows_the_order_edges_were_added() {
+        let mut graph = Graph::default();
+        let folder = add_folder(&mut graph, "src");
+        let files = ["a.rs", "b.rs", "c.rs"].map(|name| add_file(&mut graph, name));
+        for &file in files.iter().rev() {
+            add_parent_edge(&mut graph, folder, file);
+        }
+
+        assert_eq!(graph.sibling_index(files[2]), Some(0));
+        assert_eq!(graph.sibling_index(files[1]), Some(1));
+        assert_eq!(graph.sibling_index(files[0]), Some(2));
+        assert_eq!(graph.sibling_index(folder), None);
+    }
 }
@@ -0,0 +1,129 @@
+use std::fmt;
+use std::io::{self, Read};
+
+use crate::{EdgeId, Graph};
+
+/// How to handle edges whose source or target is not a node of the graph.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanEdges {
+    /// Remove them from the graph.
+    #[default]
+    Drop,
+    /// Fail with an [`OrphanEdgesError`].
+    Reject,
+}
+
+/// A graph contains edges whose source or target is not a node of the graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanEdgesError {
+    /// The orphan edges, in ascending order.
+    pub edges: Vec<EdgeId>,
+}
+
+impl fmt::Display for OrphanEdgesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Edges {:?} reference nodes that do not exist",
+            self.edges
+        )
+    }
+}
+
+impl std::error::Error for OrphanEdgesError {}
+
+impl Graph {
+    /// The edges whose source or target is not a node of the graph, in ascending order.
+    pub fn orphan_edges(&self) -> Vec<EdgeId> {
+        let mut edge_ids: Vec<_> = self
+            .edges()
+            .filter(|&edge_id| {
+                let edge = self.edge(edge_id).unwrap();
+                !self.contains_node(edge.from) || !self.contains_node(edge.to)
+            })
+            .collect();
+        edge_ids.sort_unstable();
+        edge_ids
+    }
+
+    /// Remove or reject orphan edges, which would make traversals and layouts panic.
+    /// Returns the removed edges.
+    pub fn validate(&mut self, orphan_edges: OrphanEdges) -> Result<Vec<EdgeId>, OrphanEdgesError> {
+        let edges = self.orphan_edges();
+        if edges.is_empty() {
+            return Ok(edges);
+        }
+        match orphan_edges {
+            OrphanEdges::Drop => {
+                for &edge_id in &edges {
+                    self.remove_edge(edge_id);
+                }
+                Ok(edges)
+            }
+            OrphanEdges::Reject => Err(OrphanEdgesError { edges }),
+        }
+    }
+
+    /// Load a graph serialized as JSON, such as a `graph.json` written by the analyzer,
+    /// handling edges that reference missing nodes according to `orphan_edges`.
+    pub fn read_json<R: Read>(reader: R, orphan_edges: OrphanEdges) -> io::Result<Graph> {
+        let mut graph: Graph = serde_json::from_reader(reader)?;
+        // Do not trust the serialized adjacency maps to match the edges
+        graph.reindex();
+        graph
+            .validate(orphan_edges)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{EdgeData, NodeContents, NodeData, Relation};
+
+    /// A serialized graph with a folder, a file and an edge to a node that does not exist.
+    fn json_with_dangling_edge() -> String {
+        let mut graph = Graph::default();
+        let folder = graph.add_node(NodeData::new(NodeContents::Folder {
+            display_name: "src".to_owned(),
+            path: PathBuf::from("src"),
+        }));
+        let file = graph.add_node(NodeData::new(NodeContents::File {
+            display_name: "lib.rs".to_owned(),
+            path: PathBuf::from("src/lib.rs"),
+        }));
+        graph.add_edge(EdgeData {
+            from: folder,
+            to: file,
+            relation: Relation::IsParentOf,
+        });
+        graph.add_edge(EdgeData {
+            from: folder,
+            to: file + 100,
+            relation: Relation::IsParentOf,
+        });
+        serde_json::to_string(&graph).unwrap()
+    }
+
+    #[test]
+    fn read_json_drops_orphan_edges() {
+        let json = json_with_dangling_edge();
+        let graph = Graph::read_json(json.as_bytes(), OrphanEdges::Drop).unwrap();
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.edges().count(), 1);
+        assert!(graph.orphan_edges().is_empty());
+    }
+
+    #[test]
+    fn read_json_rejects_orphan_edges() {
+        let json = json_with_dangling_edge();
+        let error = Graph::read_json(json.as_bytes(), OrphanEdges::Reject).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = error.into_inner().unwrap();
+        let error = error.downcast_ref::<OrphanEdgesError>().unwrap();
+        assert_eq!(error.edges, [1]);
+    }
+}
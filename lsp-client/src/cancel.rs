@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use jsonrpsee::core::{
+    async_trait,
+    client::{Client, ClientT, TransportSenderT},
+};
+use lsp_types::{CancelParams, NumberOrString};
+use serde::Deserialize;
+
+use crate::RpcParam;
+
+/// Separates the method of a request from the tag identifying it until it is sent.
+const TAG_SEPARATOR: char = '#';
+
+/// The IDs jsonrpsee assigned to requests sent to the server, keyed by request tag.
+pub(crate) type PendingRequests = Arc<Mutex<HashMap<u64, NumberOrString>>>;
+
+/// A sender recording the ID of every request it sends, since jsonrpsee does not expose them.
+///
+/// Requests are sent with their tag appended to the method, which the sender strips
+/// before the message reaches the server.
+pub(crate) struct TrackingSender<S> {
+    inner: S,
+    pending: PendingRequests,
+}
+
+impl<S> TrackingSender<S> {
+    pub(crate) fn new(inner: S, pending: PendingRequests) -> Self {
+        Self { inner, pending }
+    }
+
+    /// Record the ID of a tagged request and return the message without the tag.
+    ///
+    /// Only the part before the params is inspected, which jsonrpsee serializes
+    /// first, so that untagged messages are passed on without being parsed.
+    fn untag(&self, msg: &str) -> Option<String> {
+        let head = match msg.find(",\"params\":") {
+            Some(params_start) => &msg[..params_start],
+            None => msg.strip_suffix('}')?,
+        };
+        if !head.contains(TAG_SEPARATOR) {
+            return None;
+        }
+        let RequestHead { id, method } = serde_json::from_str(&format!("{}}}", head)).ok()?;
+        let (untagged, tag) = method.split_once(TAG_SEPARATOR)?;
+        let tag = tag.parse().ok()?;
+
+        let tagged = serde_json::to_string(&method).ok()?;
+        let method_start = head.find(&tagged)?;
+        let mut untagged_msg = String::with_capacity(msg.len());
+        untagged_msg.push_str(&msg[..method_start]);
+        untagged_msg.push_str(&serde_json::to_string(untagged).ok()?);
+        untagged_msg.push_str(&msg[method_start + tagged.len()..]);
+        self.pending.lock().unwrap().insert(tag, id);
+        Some(untagged_msg)
+    }
+}
+
+/// The fields of an outgoing request preceding its params.
+#[derive(Deserialize)]
+struct RequestHead {
+    id: NumberOrString,
+    method: String,
+}
+
+#[async_trait]
+impl<S: TransportSenderT> TransportSenderT for TrackingSender<S> {
+    type Error = S::Error;
+
+    async fn send(&mut self, msg: String) -> Result<(), Self::Error> {
+        let msg = self.untag(&msg).unwrap_or(msg);
+        self.inner.send(msg).await
+    }
+
+    async fn send_ping(&mut self) -> Result<(), Self::Error> {
+        self.inner.send_ping().await
+    }
+
+    async fn close(&mut self) -> Result<(), Self::Error> {
+        self.inner.close().await
+    }
+}
+
+/// Sends `$/cancelRequest` for a request if it is dropped before being completed.
+pub(crate) struct CancelOnDrop {
+    client: Arc<Client>,
+    pending: PendingRequests,
+    tag: u64,
+    completed: bool,
+}
+
+impl CancelOnDrop {
+    pub(crate) fn new(client: Arc<Client>, pending: PendingRequests) -> Self {
+        static NEXT_TAG: AtomicU64 = AtomicU64::new(0);
+        Self {
+            client,
+            pending,
+            tag: NEXT_TAG.fetch_add(1, Ordering::Relaxed),
+            completed: false,
+        }
+    }
+
+    /// The method to send the request with, so that its ID is recorded for this guard.
+    pub(crate) fn tagged_method(&self, method: &str) -> String {
+        format!("{}{}{}", method, TAG_SEPARATOR, self.tag)
+    }
+
+    /// Mark the request as answered, so that dropping the guard does not cancel it.
+    pub(crate) fn complete(mut self) {
+        self.completed = true;
+    }
+
+    /// Remove the ID of the request from the pending requests, if it was sent.
+    fn take_id(&self) -> Option<NumberOrString> {
+        self.pending.lock().unwrap().remove(&self.tag)
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        let id = self.take_id();
+        if self.completed {
+            return;
+        }
+        // The request may not have been sent yet, in which case there is nothing to cancel.
+        let (Some(id), Ok(runtime)) = (id, tokio::runtime::Handle::try_current()) else {
+            return;
+        };
+        let client = self.client.clone();
+        runtime.spawn(async move {
+            let params = CancelParams { id };
+            if let Err(error) = client
+                .notification("$/cancelRequest", RpcParam(params))
+                .await
+            {
+                tracing::debug!("Failed to cancel request: {}", error);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use lsp_types::{Position, TextDocumentIdentifier, TextDocumentPositionParams};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{memory, LspClient};
+
+    fn position_params() -> TextDocumentPositionParams {
+        TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier {
+                uri: "file:///lib.rs".parse().unwrap(),
+            },
+            position: Position::new(0, 0),
+        }
+    }
+
+    async fn next_message(server: &mut memory::MemoryServer) -> Value {
+        serde_json::from_str(&server.incoming.recv().await.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn untag_strips_the_tag_and_records_the_id() {
+        let (sender, _, _) = memory::transport();
+        let pending = PendingRequests::default();
+        let sender = TrackingSender::new(sender, pending.clone());
+
+        let request =
+            r##"{"jsonrpc":"2.0","id":3,"method":"textDocument/hover#7","params":{"a":"#1"}}"##;
+        assert_eq!(
+            sender.untag(request).unwrap(),
+            r##"{"jsonrpc":"2.0","id":3,"method":"textDocument/hover","params":{"a":"#1"}}"##
+        );
+        let without_params = r##"{"jsonrpc":"2.0","id":"x","method":"shutdown#8"}"##;
+        assert_eq!(
+            sender.untag(without_params).unwrap(),
+            r##"{"jsonrpc":"2.0","id":"x","method":"shutdown"}"##
+        );
+        let notification = r##"{"jsonrpc":"2.0","method":"initialized","params":{"a":"#1"}}"##;
+        assert_eq!(sender.untag(notification), None);
+
+        let pending = pending.lock().unwrap();
+        assert_eq!(pending[&7], NumberOrString::Number(3));
+        assert_eq!(pending[&8], NumberOrString::String("x".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn dropped_request_is_cancelled_by_its_own_id() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+
+        // Two identical requests, of which only the second is dropped
+        let mut first = Box::pin(client.text_document_moniker(position_params()));
+        let first_request = tokio::select! {
+            _ = &mut first => panic!("The request completed without a response"),
+            message = next_message(&mut server) => message,
+        };
+        let mut second = Box::pin(client.text_document_moniker(position_params()));
+        let second_request = tokio::select! {
+            _ = &mut second => panic!("The request completed without a response"),
+            message = next_message(&mut server) => message,
+        };
+        assert_eq!(first_request["method"], "textDocument/moniker");
+        assert_eq!(second_request["method"], "textDocument/moniker");
+        assert_ne!(first_request["id"], second_request["id"]);
+        drop(second);
+
+        let cancel = next_message(&mut server).await;
+        assert_eq!(cancel["method"], "$/cancelRequest");
+        assert_eq!(cancel["params"]["id"], second_request["id"]);
+
+        let response = json!({"jsonrpc": "2.0", "id": first_request["id"], "result": null});
+        server.outgoing.send(response.to_string()).unwrap();
+        assert!(first.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn completed_request_is_not_cancelled() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+
+        let mut request = Box::pin(client.text_document_moniker(position_params()));
+        let message = tokio::select! {
+            _ = &mut request => panic!("The request completed without a response"),
+            message = next_message(&mut server) => message,
+        };
+        let response = json!({"jsonrpc": "2.0", "id": message["id"], "result": null});
+        server.outgoing.send(response.to_string()).unwrap();
+        assert!(request.await.unwrap().is_empty());
+        assert!(client.pending_requests.lock().unwrap().is_empty());
+
+        let next = tokio::time::timeout(Duration::from_millis(50), server.incoming.recv()).await;
+        assert!(next.is_err(), "Unexpected message after the response");
+    }
+}
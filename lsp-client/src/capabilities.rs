@@ -0,0 +1,70 @@
+use lsp_types::{HoverProviderCapability, OneOf, ServerCapabilities};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("The server does not support {method}")]
+pub struct UnsupportedByServer {
+    pub method: String,
+}
+
+/// Whether the server advertised support for an optional method.
+/// Returns `None` for methods that are not covered by a capability.
+pub(crate) fn supports(capabilities: &ServerCapabilities, method: &str) -> Option<bool> {
+    let supported = match method {
+        "workspace/symbol" => enabled(&capabilities.workspace_symbol_provider),
+        "workspaceSymbol/resolve" => matches!(
+            &capabilities.workspace_symbol_provider,
+            Some(OneOf::Right(options)) if options.resolve_provider == Some(true)
+        ),
+        "textDocument/documentSymbol" => enabled(&capabilities.document_symbol_provider),
+        "textDocument/moniker" => enabled(&capabilities.moniker_provider),
+        "textDocument/hover" => matches!(
+            capabilities.hover_provider,
+            Some(HoverProviderCapability::Simple(true) | HoverProviderCapability::Options(_))
+        ),
+        "textDocument/codeLens" => capabilities.code_lens_provider.is_some(),
+        "codeLens/resolve" => matches!(
+            &capabilities.code_lens_provider,
+            Some(options) if options.resolve_provider == Some(true)
+        ),
+        "textDocument/rename" => enabled(&capabilities.rename_provider),
+        "textDocument/prepareRename" => matches!(
+            &capabilities.rename_provider,
+            Some(OneOf::Right(options)) if options.prepare_provider == Some(true)
+        ),
+        _ => return None,
+    };
+    Some(supported)
+}
+
+fn enabled<T>(provider: &Option<OneOf<bool, T>>) -> bool {
+    matches!(provider, Some(OneOf::Left(true) | OneOf::Right(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{RenameOptions, WorkDoneProgressOptions};
+
+    use super::*;
+
+    #[test]
+    fn optional_methods_need_their_capability() {
+        let capabilities = ServerCapabilities {
+            document_symbol_provider: Some(OneOf::Left(true)),
+            moniker_provider: Some(OneOf::Left(false)),
+            rename_provider: Some(OneOf::Right(RenameOptions {
+                prepare_provider: None,
+                work_done_progress_options: WorkDoneProgressOptions::default(),
+            })),
+            ..ServerCapabilities::default()
+        };
+
+        let supports = |method| supports(&capabilities, method);
+        assert_eq!(supports("textDocument/documentSymbol"), Some(true));
+        assert_eq!(supports("textDocument/moniker"), Some(false));
+        assert_eq!(supports("workspace/symbol"), Some(false));
+        assert_eq!(supports("textDocument/rename"), Some(true));
+        assert_eq!(supports("textDocument/prepareRename"), Some(false));
+        assert_eq!(supports("custom/request"), None);
+    }
+}
@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use jsonrpsee::core::client::Client;
+use jsonrpsee::core::client::Subscription;
+use jsonrpsee::core::client::SubscriptionClientT;
+use lsp_types::PublishDiagnosticsParams;
+
+/// The latest published diagnostics, keyed by document URI.
+pub(crate) type DiagnosticsStore = Arc<Mutex<HashMap<String, PublishDiagnosticsParams>>>;
+
+/// Subscribe to `textDocument/publishDiagnostics` and record the latest diagnostics per document.
+pub(crate) async fn collect_diagnostics(
+    client: &Client,
+    store: DiagnosticsStore,
+) -> Result<(), anyhow::Error> {
+    let mut subscription: Subscription<PublishDiagnosticsParams> = client
+        .subscribe_to_method("textDocument/publishDiagnostics")
+        .await?;
+
+    tokio::spawn(async move {
+        while let Some(Ok(params)) = subscription.next().await {
+            let uri = params.uri.as_str().to_owned();
+            store.lock().unwrap().insert(uri, params);
+        }
+    });
+
+    Ok(())
+}
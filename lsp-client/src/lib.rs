@@ -1,6 +1,10 @@
-use std::ffi::OsStr;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
 use anyhow::anyhow;
+use futures::{Stream, StreamExt, TryStreamExt};
 use jsonrpsee::core::client::Client;
 use jsonrpsee::core::client::ClientBuilder;
 use jsonrpsee::core::client::ClientT;
@@ -12,28 +16,101 @@ use lsp_types::{
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use tokio::process;
+use tokio::sync::Semaphore;
 
+pub use jsonrpsee::core::async_trait;
+pub use jsonrpsee::core::client::{ReceivedMessage, TransportReceiverT, TransportSenderT};
 pub use lsp_types;
 
-pub mod progress;
+pub use capabilities::UnsupportedByServer;
+
+mod cancel;
+mod capabilities;
+mod diagnostics;
+#[cfg(any(test, feature = "memory-transport"))]
+pub mod memory;
+mod partial;
+mod progress;
+pub mod stderr;
 mod transport;
 
-pub struct LspClient {
-    /// The LSP server process.
-    #[allow(dead_code)]
-    child: process::Child,
-    /// JSONRPC connection to the LSP server.
-    jsonrpc_client: Client,
+/// The default number of requests that may be in flight at the same time.
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 16;
+
+pub struct LspClientBuilder {
+    /// The LSP server program to spawn.
+    program: OsString,
+    /// Arguments passed to the program.
+    args: Vec<OsString>,
+    /// Environment variables set for the program in addition to the inherited ones.
+    envs: Vec<(OsString, OsString)>,
+    /// The maximum number of requests that may be in flight at the same time.
+    max_concurrent_requests: usize,
+    /// Skip malformed messages from the server instead of closing the connection.
+    resync_on_framing_error: bool,
 }
 
-impl LspClient {
-    /// Start an LSP server and returns a client for interacting with it.
-    pub fn start<S: AsRef<OsStr>>(program: S) -> Result<Self, anyhow::Error> {
-        let program = program.as_ref().to_owned();
-        let mut command = process::Command::new(&program);
+impl LspClientBuilder {
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
+        Self {
+            program: program.as_ref().to_owned(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            resync_on_framing_error: false,
+        }
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    pub fn envs<I, K, V>(mut self, envs: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs.extend(
+            envs.into_iter()
+                .map(|(key, value)| (key.as_ref().to_owned(), value.as_ref().to_owned())),
+        );
+        self
+    }
+
+    /// Cap the number of concurrently in-flight requests.
+    /// Defaults to [`DEFAULT_MAX_CONCURRENT_REQUESTS`].
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests;
+        self
+    }
+
+    /// Skip a message with a malformed header or a body that is not UTF-8 and continue
+    /// with the next one, instead of closing the connection. Off by default, since
+    /// skipped messages may leave requests without a response and hide server bugs.
+    pub fn resync_on_framing_error(mut self, resync_on_framing_error: bool) -> Self {
+        self.resync_on_framing_error = resync_on_framing_error;
+        self
+    }
+
+    /// Start the LSP server and return a client for interacting with it.
+    pub fn start(self) -> Result<LspClient, anyhow::Error> {
+        if self.max_concurrent_requests == 0 {
+            anyhow::bail!("The maximum number of concurrent requests must be at least 1");
+        }
+        let mut command = process::Command::new(&self.program);
         command
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(key, value)| (key, value)))
             .stdout(std::process::Stdio::piped())
-            .stdin(std::process::Stdio::piped());
+            .stdin(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
         let mut child = command.spawn()?;
 
         let stdout = child
@@ -46,15 +123,129 @@ impl LspClient {
             .take()
             .ok_or_else(|| anyhow!("Failed to acquire child stdin"))?;
 
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("Failed to acquire child stderr"))?;
+        let stderr_lines = Arc::new(Mutex::new(VecDeque::new()));
+        stderr::forward_stderr(stderr, stderr_lines.clone());
+
         let sender = transport::StdioSender::new(stdin);
-        let receiver = transport::StdioReceiver::new(stdout);
+        let receiver = transport::StdioReceiver::new(stdout, self.resync_on_framing_error);
+
+        let mut client = self.build_with_transport(sender, receiver)?;
+        client.child = Some(child);
+        client.stderr_lines = stderr_lines;
+        Ok(client)
+    }
 
+    /// Return a client communicating over the given transport instead of spawning a server.
+    /// The program, arguments and environment variables are ignored.
+    pub fn build_with_transport<S, R>(
+        self,
+        sender: S,
+        receiver: R,
+    ) -> Result<LspClient, anyhow::Error>
+    where
+        S: TransportSenderT + Send,
+        R: TransportReceiverT + Send,
+    {
+        if self.max_concurrent_requests == 0 {
+            anyhow::bail!("The maximum number of concurrent requests must be at least 1");
+        }
+        Ok(LspClient::with_transport(
+            sender,
+            receiver,
+            self.max_concurrent_requests,
+        ))
+    }
+}
+
+/// How the LSP server process terminated in [`LspClient::stop`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    /// The server exited after the shutdown handshake.
+    Clean,
+    /// The server did not exit in time and was killed.
+    Killed,
+}
+
+/// A client for an LSP server.
+///
+/// Dropping the future of a pending request sends `$/cancelRequest` to the server.
+/// Servers are free to ignore cancellation and finish the request anyway.
+pub struct LspClient {
+    /// The LSP server process, if the client spawned it.
+    child: Option<process::Child>,
+    /// JSONRPC connection to the LSP server.
+    jsonrpc_client: Arc<Client>,
+    /// The IDs of requests sent to the server, used to cancel them.
+    pending_requests: cancel::PendingRequests,
+    /// Permits bounding the number of concurrently in-flight requests.
+    request_permits: Semaphore,
+    /// The most recent lines the LSP server wrote to stderr.
+    stderr_lines: Arc<Mutex<VecDeque<String>>>,
+    /// The latest diagnostics published by the LSP server.
+    diagnostics: diagnostics::DiagnosticsStore,
+    /// The single `$/progress` subscription shared by all requests.
+    progress: progress::ProgressDispatcher,
+    /// The capabilities the server reported when it was initialized.
+    server_capabilities: OnceLock<lsp_types::ServerCapabilities>,
+}
+
+impl LspClient {
+    /// Start an LSP server and returns a client for interacting with it.
+    pub fn start<S: AsRef<OsStr>>(program: S) -> Result<Self, anyhow::Error> {
+        LspClientBuilder::new(program).start()
+    }
+
+    /// Start an LSP server with the given arguments and additional environment variables.
+    pub fn start_with_env<S, I, A, E>(program: S, args: I, envs: E) -> Result<Self, anyhow::Error>
+    where
+        S: AsRef<OsStr>,
+        I: IntoIterator<Item = A>,
+        A: AsRef<OsStr>,
+        E: IntoIterator<Item = (OsString, OsString)>,
+    {
+        LspClientBuilder::new(program).args(args).envs(envs).start()
+    }
+
+    /// Return a client communicating with an LSP server over a custom transport.
+    pub fn from_transport<S, R>(sender: S, receiver: R) -> Self
+    where
+        S: TransportSenderT + Send,
+        R: TransportReceiverT + Send,
+    {
+        Self::with_transport(sender, receiver, DEFAULT_MAX_CONCURRENT_REQUESTS)
+    }
+
+    fn with_transport<S, R>(sender: S, receiver: R, max_concurrent_requests: usize) -> Self
+    where
+        S: TransportSenderT + Send,
+        R: TransportReceiverT + Send,
+    {
+        let pending_requests = cancel::PendingRequests::default();
+        let sender = cancel::TrackingSender::new(sender, pending_requests.clone());
         let jsonrpc_client = ClientBuilder::default().build_with_tokio(sender, receiver);
+        Self {
+            child: None,
+            jsonrpc_client: Arc::new(jsonrpc_client),
+            pending_requests,
+            request_permits: Semaphore::new(max_concurrent_requests),
+            stderr_lines: Arc::new(Mutex::new(VecDeque::new())),
+            diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            progress: progress::ProgressDispatcher::default(),
+            server_capabilities: OnceLock::new(),
+        }
+    }
 
-        Ok(Self {
-            child,
-            jsonrpc_client,
-        })
+    pub fn builder<S: AsRef<OsStr>>(program: S) -> LspClientBuilder {
+        LspClientBuilder::new(program)
+    }
+
+    /// The most recent raw lines the LSP server wrote to stderr.
+    pub fn stderr_lines(&self) -> Vec<String> {
+        self.stderr_lines.lock().unwrap().iter().cloned().collect()
     }
 
     pub async fn initialize<F: FnOnce(InitializeResult) -> InitializedParams>(
@@ -63,13 +254,55 @@ impl LspClient {
         on_initialized: F,
     ) -> Result<(), anyhow::Error> {
         let result: InitializeResult = self.request("initialize", params).await?;
+        let _ = self.server_capabilities.set(result.capabilities.clone());
         let initialized_params = on_initialized(result);
         self.notify("initialized", initialized_params).await?;
         Ok(())
     }
 
+    /// The capabilities the server reported, or `None` before initialization.
+    pub fn server_capabilities(&self) -> Option<&lsp_types::ServerCapabilities> {
+        self.server_capabilities.get()
+    }
+
+    /// Start recording the diagnostics published by the server.
+    /// Call this before initializing so that no diagnostics are missed.
+    pub async fn collect_diagnostics(&self) -> Result<(), anyhow::Error> {
+        diagnostics::collect_diagnostics(&self.jsonrpc_client, self.diagnostics.clone()).await
+    }
+
+    /// The latest diagnostics for every document the server published diagnostics for.
+    pub fn diagnostics(&self) -> Vec<lsp_types::PublishDiagnosticsParams> {
+        self.diagnostics.lock().unwrap().values().cloned().collect()
+    }
+
     pub async fn wait_for_indexing_to_complete(&self) -> Result<(), anyhow::Error> {
-        progress::wait_for_indexing_to_complete(&self.jsonrpc_client).await
+        self.wait_for_indexing_to_complete_with_progress(|_, _, _| {})
+            .await
+    }
+
+    /// Wait for indexing, invoking `on_report` with the token, percentage and message
+    /// of every progress report.
+    pub async fn wait_for_indexing_to_complete_with_progress<F>(
+        &self,
+        on_report: F,
+    ) -> Result<(), anyhow::Error>
+    where
+        F: FnMut(&lsp_types::NumberOrString, Option<u32>, Option<&str>),
+    {
+        let notifications = self.progress.work_done(&self.jsonrpc_client).await?;
+        progress::indexing(notifications, on_report).await
+    }
+
+    /// Wait until the server reported no active progress for `quiet_for`, failing
+    /// if that has not happened within `timeout`.
+    pub async fn wait_for_idle(
+        &self,
+        quiet_for: Duration,
+        timeout: Duration,
+    ) -> Result<(), anyhow::Error> {
+        let notifications = self.progress.work_done(&self.jsonrpc_client).await?;
+        progress::idle(notifications, quiet_for, timeout).await
     }
 
     pub async fn workspace_symbol(
@@ -79,6 +312,31 @@ impl LspClient {
         self.request("workspace/symbol", params).await
     }
 
+    /// Query workspace symbols, yielding partial results as the server reports them.
+    /// Falls back to the single final response if the server does not stream results.
+    pub async fn workspace_symbol_streaming(
+        &self,
+        mut params: WorkspaceSymbolParams,
+    ) -> Result<
+        impl Stream<Item = Result<lsp_types::WorkspaceSymbol, anyhow::Error>> + '_,
+        anyhow::Error,
+    > {
+        let token = partial::fresh_token();
+        params.partial_result_params.partial_result_token = Some(token.clone());
+        let listener = self
+            .progress
+            .partial_results(&self.jsonrpc_client, token)
+            .await?;
+        let responses = partial::partial_results(listener, self.workspace_symbol(params));
+        Ok(responses.flat_map(|response| {
+            let symbols = match response {
+                Ok(response) => workspace_symbols(response).into_iter().map(Ok).collect(),
+                Err(error) => vec![Err(error)],
+            };
+            futures::stream::iter(symbols)
+        }))
+    }
+
     pub async fn workspace_symbol_resolve(
         &self,
         params: lsp_types::WorkspaceSymbol,
@@ -86,6 +344,21 @@ impl LspClient {
         self.request("workspaceSymbol/resolve", params).await
     }
 
+    /// Query workspace symbols, resolving those returned without a range in their location.
+    pub async fn workspace_symbol_resolved(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Vec<lsp_types::WorkspaceSymbol>, anyhow::Error> {
+        let symbols = workspace_symbols(self.workspace_symbol(params).await?);
+        futures::future::try_join_all(symbols.into_iter().map(|symbol| async move {
+            match symbol.location {
+                lsp_types::OneOf::Left(_) => Ok(symbol),
+                lsp_types::OneOf::Right(_) => self.workspace_symbol_resolve(symbol).await,
+            }
+        }))
+        .await
+    }
+
     pub async fn document_symbol(
         &self,
         params: lsp_types::DocumentSymbolParams,
@@ -93,11 +366,72 @@ impl LspClient {
         self.request("textDocument/documentSymbol", params).await
     }
 
+    /// Query document symbols, merging the partial results the server reports with
+    /// its final response. Servers that do not stream results answer all at once.
+    pub async fn document_symbol_partial(
+        &self,
+        mut params: lsp_types::DocumentSymbolParams,
+    ) -> Result<lsp_types::DocumentSymbolResponse, anyhow::Error> {
+        let token = partial::fresh_token();
+        params.partial_result_params.partial_result_token = Some(token.clone());
+        let listener = self
+            .progress
+            .partial_results(&self.jsonrpc_client, token)
+            .await?;
+        let responses: Vec<lsp_types::DocumentSymbolResponse> =
+            partial::partial_results(listener, self.document_symbol(params))
+                .try_collect()
+                .await?;
+        Ok(merge_document_symbols(responses))
+    }
+
+    pub async fn hover(
+        &self,
+        params: lsp_types::HoverParams,
+    ) -> Result<Option<lsp_types::Hover>, anyhow::Error> {
+        self.request("textDocument/hover", params).await
+    }
+
+    /// The monikers of the symbol at a position, empty if the server returns `null`.
     pub async fn text_document_moniker(
         &self,
         params: lsp_types::TextDocumentPositionParams,
-    ) -> Result<lsp_types::Moniker, anyhow::Error> {
-        self.request("textDocument/moniker", params).await
+    ) -> Result<Vec<lsp_types::Moniker>, anyhow::Error> {
+        let monikers: Option<Vec<lsp_types::Moniker>> =
+            self.request("textDocument/moniker", params).await?;
+        Ok(monikers.unwrap_or_default())
+    }
+
+    /// The code lenses of a document, empty if the server returns `null`.
+    /// Lenses without a command must be resolved with [`LspClient::code_lens_resolve`].
+    pub async fn code_lens(
+        &self,
+        params: lsp_types::CodeLensParams,
+    ) -> Result<Vec<lsp_types::CodeLens>, anyhow::Error> {
+        let lenses: Option<Vec<lsp_types::CodeLens>> =
+            self.request("textDocument/codeLens", params).await?;
+        Ok(lenses.unwrap_or_default())
+    }
+
+    pub async fn code_lens_resolve(
+        &self,
+        lens: lsp_types::CodeLens,
+    ) -> Result<lsp_types::CodeLens, anyhow::Error> {
+        self.request("codeLens/resolve", lens).await
+    }
+
+    pub async fn prepare_rename(
+        &self,
+        params: lsp_types::TextDocumentPositionParams,
+    ) -> Result<Option<lsp_types::PrepareRenameResponse>, anyhow::Error> {
+        self.request("textDocument/prepareRename", params).await
+    }
+
+    pub async fn rename(
+        &self,
+        params: lsp_types::RenameParams,
+    ) -> Result<Option<lsp_types::WorkspaceEdit>, anyhow::Error> {
+        self.request("textDocument/rename", params).await
     }
 
     pub async fn did_open(
@@ -107,6 +441,27 @@ impl LspClient {
         self.notify("textDocument/didOpen", params).await
     }
 
+    pub async fn did_change(
+        &self,
+        params: lsp_types::DidChangeTextDocumentParams,
+    ) -> Result<(), anyhow::Error> {
+        self.notify("textDocument/didChange", params).await
+    }
+
+    pub async fn did_close(
+        &self,
+        params: lsp_types::DidCloseTextDocumentParams,
+    ) -> Result<(), anyhow::Error> {
+        self.notify("textDocument/didClose", params).await
+    }
+
+    pub async fn did_change_watched_files(
+        &self,
+        params: lsp_types::DidChangeWatchedFilesParams,
+    ) -> Result<(), anyhow::Error> {
+        self.notify("workspace/didChangeWatchedFiles", params).await
+    }
+
     pub async fn shutdown(&self) -> Result<(), anyhow::Error> {
         self.request("shutdown", serde_json::Value::Null).await
     }
@@ -115,16 +470,81 @@ impl LspClient {
         self.notify("exit", ()).await
     }
 
+    /// Perform the shutdown handshake and wait for the server to exit, each within
+    /// `timeout`. Kills the server process if it does not exit in time.
+    pub async fn stop(mut self, timeout: Duration) -> Result<Shutdown, anyhow::Error> {
+        let handshake = async {
+            self.shutdown().await?;
+            self.exit().await
+        };
+        // A failed or hanging handshake is handled by killing the process below
+        let handshake = tokio::time::timeout(timeout, handshake).await;
+
+        let Some(child) = self.child.as_mut() else {
+            // Without a process, only the handshake tells whether the server complied
+            return match handshake {
+                Ok(Ok(())) => Ok(Shutdown::Clean),
+                Ok(Err(error)) => Err(error),
+                Err(_) => Err(anyhow!("Timed out waiting for the server to shut down")),
+            };
+        };
+        match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(Ok(_)) => Ok(Shutdown::Clean),
+            _ => {
+                child.kill().await?;
+                Ok(Shutdown::Killed)
+            }
+        }
+    }
+
+    /// Kill the server process without the shutdown handshake, if the client spawned it.
+    pub async fn kill(mut self) -> Result<(), anyhow::Error> {
+        if let Some(child) = self.child.as_mut() {
+            child.kill().await?;
+        }
+        Ok(())
+    }
+
+    /// Send several requests of the same method without waiting for each response
+    /// before sending the next, returning the results in the order of `params`.
+    ///
+    /// The requests are pipelined rather than sent as a JSON-RPC batch, because the
+    /// LSP base protocol does not support batches and servers such as rust-analyzer
+    /// reject them. At most `max_concurrent_requests` are in flight at the same time.
+    pub async fn batch<T: Serialize + Send, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: impl IntoIterator<Item = T>,
+    ) -> Vec<Result<R, anyhow::Error>> {
+        let requests = params
+            .into_iter()
+            .map(|params| self.request(method, params));
+        futures::future::join_all(requests).await
+    }
+
     async fn request<T: Serialize + Send, R: DeserializeOwned>(
         &self,
         method: &str,
         params: T,
     ) -> Result<R, anyhow::Error> {
+        // Without known capabilities, the request is attempted anyway
+        if let Some(capabilities) = self.server_capabilities.get() {
+            if capabilities::supports(capabilities, method) == Some(false) {
+                return Err(UnsupportedByServer {
+                    method: method.to_owned(),
+                }
+                .into());
+            }
+        }
+        let _permit = self.request_permits.acquire().await?;
+        let guard =
+            cancel::CancelOnDrop::new(self.jsonrpc_client.clone(), self.pending_requests.clone());
         let response = self
             .jsonrpc_client
-            .request(method, RpcParam(params))
-            .await?;
-        Ok(response)
+            .request(&guard.tagged_method(method), RpcParam(params))
+            .await;
+        guard.complete();
+        Ok(response?)
     }
 
     async fn notify<T: Serialize + Send>(
@@ -139,6 +559,44 @@ impl LspClient {
     }
 }
 
+/// The symbols of a workspace symbol response, converting flat symbol information.
+fn workspace_symbols(response: WorkspaceSymbolResponse) -> Vec<lsp_types::WorkspaceSymbol> {
+    match response {
+        WorkspaceSymbolResponse::Flat(symbols) => symbols
+            .into_iter()
+            .map(|symbol| lsp_types::WorkspaceSymbol {
+                name: symbol.name,
+                kind: symbol.kind,
+                tags: symbol.tags,
+                container_name: symbol.container_name,
+                location: lsp_types::OneOf::Left(symbol.location),
+                data: None,
+            })
+            .collect(),
+        WorkspaceSymbolResponse::Nested(symbols) => symbols,
+    }
+}
+
+/// Concatenate document symbol responses. Empty responses deserialize as flat, so the
+/// response is nested if any of the parts is.
+fn merge_document_symbols(
+    responses: Vec<lsp_types::DocumentSymbolResponse>,
+) -> lsp_types::DocumentSymbolResponse {
+    let mut flat = Vec::new();
+    let mut nested = Vec::new();
+    for response in responses {
+        match response {
+            lsp_types::DocumentSymbolResponse::Flat(symbols) => flat.extend(symbols),
+            lsp_types::DocumentSymbolResponse::Nested(symbols) => nested.extend(symbols),
+        }
+    }
+    if nested.is_empty() {
+        lsp_types::DocumentSymbolResponse::Flat(flat)
+    } else {
+        lsp_types::DocumentSymbolResponse::Nested(nested)
+    }
+}
+
 /// Wrapper type for a single RPC parameter.
 struct RpcParam<S: serde::Serialize + Send>(S);
 
@@ -148,3 +606,327 @@ impl<S: serde::Serialize + Send> ToRpcParams for RpcParam<S> {
         Ok(Some(raw_value))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    async fn next_message(server: &mut memory::MemoryServer) -> Value {
+        serde_json::from_str(&server.incoming.recv().await.unwrap()).unwrap()
+    }
+
+    fn respond(server: &memory::MemoryServer, request: &Value, result: Value) {
+        let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+        server.outgoing.send(response.to_string()).unwrap();
+    }
+
+    #[test]
+    fn zero_concurrent_requests_are_rejected() {
+        let result = LspClient::builder("unused")
+            .max_concurrent_requests(0)
+            .start();
+        assert!(result.is_err());
+    }
+
+    /// Write an executable shell script standing in for a server to a fresh directory.
+    #[cfg(unix)]
+    fn server_script(name: &str, script: &str) -> (PathBuf, PathBuf) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("lsp-client-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let server = dir.join("server.sh");
+        fs::write(&server, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&server, fs::Permissions::from_mode(0o755)).unwrap();
+        (dir, server)
+    }
+
+    /// Requests beyond the limit are only sent once a permit is free,
+    /// so a server that never answers sees no more than the limit.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn requests_beyond_the_limit_wait_for_a_permit() {
+        // Without `exec`, the shell keeps stdout open, so the client does not see an EOF
+        let (dir, server) = server_script("permits", "cat > \"$(dirname \"$0\")/requests.log\"");
+        let log = dir.join("requests.log");
+
+        let client = LspClient::builder(&server)
+            .max_concurrent_requests(2)
+            .start()
+            .unwrap();
+        let requests = (0..5).map(|_| client.request::<_, serde_json::Value>("custom/request", ()));
+        let outcome = tokio::time::timeout(
+            Duration::from_millis(200),
+            futures::future::join_all(requests),
+        )
+        .await;
+        let sent = fs::read_to_string(&log).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(outcome.is_err());
+        assert_eq!(sent.matches("custom/request").count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stop_reports_a_server_that_exited() {
+        let (dir, server) = server_script("exited", "exit 0");
+        let client = LspClient::start(&server).unwrap();
+        let shutdown = client.stop(Duration::from_millis(500)).await;
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(shutdown.unwrap(), Shutdown::Clean);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stop_kills_an_unresponsive_server() {
+        let (dir, server) = server_script("unresponsive", "exec sleep 60");
+        let client = LspClient::start(&server).unwrap();
+        let shutdown = client.stop(Duration::from_millis(100)).await;
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(shutdown.unwrap(), Shutdown::Killed);
+    }
+
+    #[tokio::test]
+    async fn initialize_over_memory_transport() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let server = tokio::spawn(async move {
+            let request = next_message(&mut server).await;
+            assert_eq!(request["method"], "initialize");
+            respond(
+                &server,
+                &request,
+                json!({"capabilities": {"hoverProvider": true}}),
+            );
+            next_message(&mut server).await
+        });
+
+        let mut capabilities = None;
+        client
+            .initialize(InitializeParams::default(), |result| {
+                capabilities = Some(result.capabilities);
+                InitializedParams {}
+            })
+            .await
+            .unwrap();
+        let notification = server.await.unwrap();
+        assert_eq!(notification["method"], "initialized");
+        assert!(capabilities.unwrap().hover_provider.is_some());
+    }
+
+    #[tokio::test]
+    async fn moniker_response_may_be_a_list_or_null() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let monikers = json!([{
+            "scheme": "rust-analyzer",
+            "identifier": "crate::main",
+            "unique": "scheme",
+            "kind": "export",
+        }]);
+        let server = tokio::spawn(async move {
+            for result in [monikers, Value::Null] {
+                let request = next_message(&mut server).await;
+                assert_eq!(request["method"], "textDocument/moniker");
+                respond(&server, &request, result);
+            }
+        });
+
+        let position = lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: "file:///lib.rs".parse().unwrap(),
+            },
+            position: lsp_types::Position::new(0, 3),
+        };
+        let monikers = client
+            .text_document_moniker(position.clone())
+            .await
+            .unwrap();
+        assert_eq!(monikers.len(), 1);
+        assert_eq!(monikers[0].identifier, "crate::main");
+        let monikers = client.text_document_moniker(position).await.unwrap();
+        assert!(monikers.is_empty());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn workspace_symbols_without_a_range_are_resolved() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let range =
+            json!({"start": {"line": 1, "character": 0}, "end": {"line": 3, "character": 1}});
+        let server = tokio::spawn(async move {
+            let request = next_message(&mut server).await;
+            assert_eq!(request["method"], "workspace/symbol");
+            let symbols = json!([
+                {"name": "main", "kind": 12, "location": {"uri": "file:///main.rs", "range": range}},
+                {"name": "lib", "kind": 12, "location": {"uri": "file:///lib.rs"}},
+            ]);
+            respond(&server, &request, symbols);
+
+            let request = next_message(&mut server).await;
+            assert_eq!(request["method"], "workspaceSymbol/resolve");
+            assert_eq!(request["params"]["name"], "lib");
+            let mut resolved = request["params"].clone();
+            resolved["location"]["range"] = range;
+            respond(&server, &request, resolved);
+        });
+
+        let symbols = client
+            .workspace_symbol_resolved(WorkspaceSymbolParams::default())
+            .await
+            .unwrap();
+        server.await.unwrap();
+        assert_eq!(symbols.len(), 2);
+        for symbol in symbols {
+            let lsp_types::OneOf::Left(location) = symbol.location else {
+                panic!("{} was not resolved", symbol.name);
+            };
+            assert_eq!(location.range.end, lsp_types::Position::new(3, 1));
+        }
+    }
+
+    /// A partial result the server sends right before its response is merged into it,
+    /// however the client's tasks are scheduled.
+    #[tokio::test]
+    async fn document_symbol_partial_merges_results_sent_before_the_response() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let symbol = |name: &str| {
+            let range =
+                json!({"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 1}});
+            json!({"name": name, "kind": 12, "range": range, "selectionRange": range})
+        };
+        let requests = 10;
+        let server = tokio::spawn(async move {
+            for _ in 0..requests {
+                let request = next_message(&mut server).await;
+                assert_eq!(request["method"], "textDocument/documentSymbol");
+                let token = &request["params"]["partialResultToken"];
+                let progress = json!({
+                    "jsonrpc": "2.0",
+                    "method": "$/progress",
+                    "params": {"token": token, "value": [symbol("partial")]},
+                });
+                server.outgoing.send(progress.to_string()).unwrap();
+                respond(&server, &request, json!([symbol("final")]));
+            }
+        });
+
+        for _ in 0..requests {
+            let params = lsp_types::DocumentSymbolParams {
+                text_document: lsp_types::TextDocumentIdentifier {
+                    uri: "file:///lib.rs".parse().unwrap(),
+                },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            };
+            let response = client.document_symbol_partial(params).await.unwrap();
+            let lsp_types::DocumentSymbolResponse::Nested(symbols) = response else {
+                panic!("Expected nested symbols");
+            };
+            let names: Vec<_> = symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+            assert_eq!(names, ["partial", "final"]);
+        }
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unadvertised_methods_fail_without_a_request() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let server = tokio::spawn(async move {
+            let request = next_message(&mut server).await;
+            respond(&server, &request, json!({"capabilities": {}}));
+            let initialized = next_message(&mut server).await;
+            assert_eq!(initialized["method"], "initialized");
+            server
+        });
+        client
+            .initialize(InitializeParams::default(), |_| InitializedParams {})
+            .await
+            .unwrap();
+        let mut server = server.await.unwrap();
+
+        let position = lsp_types::TextDocumentPositionParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: "file:///lib.rs".parse().unwrap(),
+            },
+            position: lsp_types::Position::new(0, 3),
+        };
+        let error = client.text_document_moniker(position).await.unwrap_err();
+        let unsupported = error.downcast::<UnsupportedByServer>().unwrap();
+        assert_eq!(unsupported.method, "textDocument/moniker");
+        assert!(server.incoming.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn code_lenses_are_listed_and_resolved() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let range =
+            json!({"start": {"line": 1, "character": 3}, "end": {"line": 1, "character": 7}});
+        let server = tokio::spawn(async move {
+            let request = next_message(&mut server).await;
+            assert_eq!(request["method"], "textDocument/codeLens");
+            respond(&server, &request, json!([{"range": range, "data": 1}]));
+
+            let request = next_message(&mut server).await;
+            assert_eq!(request["method"], "codeLens/resolve");
+            assert_eq!(request["params"]["data"], 1);
+            let mut resolved = request["params"].clone();
+            resolved["command"] = json!({"title": "2 references", "command": ""});
+            respond(&server, &request, resolved);
+
+            let request = next_message(&mut server).await;
+            respond(&server, &request, Value::Null);
+        });
+
+        let params = || lsp_types::CodeLensParams {
+            text_document: lsp_types::TextDocumentIdentifier {
+                uri: "file:///main.rs".parse().unwrap(),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let lenses = client.code_lens(params()).await.unwrap();
+        assert_eq!(lenses.len(), 1);
+        assert!(lenses[0].command.is_none());
+        let resolved = client.code_lens_resolve(lenses[0].clone()).await.unwrap();
+        assert_eq!(resolved.command.unwrap().title, "2 references");
+        assert_eq!(resolved.range, lenses[0].range);
+        assert!(client.code_lens(params()).await.unwrap().is_empty());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn batch_results_follow_the_order_of_the_params() {
+        let (sender, receiver, mut server) = memory::transport();
+        let client = LspClient::from_transport(sender, receiver);
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                requests.push(next_message(&mut server).await);
+            }
+            // All requests arrive before the first is answered, answer them in reverse
+            for request in requests.iter().rev() {
+                respond(&server, request, request["params"]["line"].clone());
+            }
+        });
+
+        let params = (0..3).map(|line| json!({"line": line}));
+        let results: Vec<Result<u32, _>> = client.batch("custom/request", params).await;
+        server.await.unwrap();
+        let results: Vec<_> = results.into_iter().map(Result::unwrap).collect();
+        assert_eq!(results, [0, 1, 2]);
+    }
+}
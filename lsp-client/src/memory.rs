@@ -0,0 +1,60 @@
+use jsonrpsee::core::{
+    async_trait,
+    client::{ReceivedMessage, TransportReceiverT, TransportSenderT},
+};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Error)]
+#[error("The other end of the in-memory transport was dropped")]
+pub struct Disconnected;
+
+pub struct MemorySender {
+    sender: mpsc::UnboundedSender<String>,
+}
+
+pub struct MemoryReceiver {
+    receiver: mpsc::UnboundedReceiver<String>,
+}
+
+/// The server end of an in-memory transport, exchanging raw JSON-RPC messages.
+pub struct MemoryServer {
+    /// Messages sent by the client.
+    pub incoming: mpsc::UnboundedReceiver<String>,
+    /// Messages delivered to the client.
+    pub outgoing: mpsc::UnboundedSender<String>,
+}
+
+/// Create a connected client transport pair and the corresponding server end.
+pub fn transport() -> (MemorySender, MemoryReceiver, MemoryServer) {
+    let (client_sender, incoming) = mpsc::unbounded_channel();
+    let (outgoing, client_receiver) = mpsc::unbounded_channel();
+    (
+        MemorySender {
+            sender: client_sender,
+        },
+        MemoryReceiver {
+            receiver: client_receiver,
+        },
+        MemoryServer { incoming, outgoing },
+    )
+}
+
+#[async_trait]
+impl TransportSenderT for MemorySender {
+    type Error = Disconnected;
+
+    async fn send(&mut self, msg: String) -> Result<(), Self::Error> {
+        self.sender.send(msg).map_err(|_| Disconnected)
+    }
+}
+
+#[async_trait]
+impl TransportReceiverT for MemoryReceiver {
+    type Error = Disconnected;
+
+    async fn receive(&mut self) -> Result<ReceivedMessage, Self::Error> {
+        let msg = self.receiver.recv().await.ok_or(Disconnected)?;
+        Ok(ReceivedMessage::Text(msg))
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::future::{self, Either};
+use futures::Stream;
+use lsp_types::{NumberOrString, ProgressToken};
+use serde::de::DeserializeOwned;
+
+use crate::progress::PartialResultListener;
+
+/// A progress token not used by any other request of this client.
+pub(crate) fn fresh_token() -> ProgressToken {
+    static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+    let id = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    NumberOrString::String(format!("lsp-client/partial-result/{}", id))
+}
+
+/// Stream the partial results reported to `listener` while `request` is pending,
+/// followed by the final result of the request. Servers that do not support
+/// partial results simply produce the final result.
+pub(crate) fn partial_results<'a, F, R>(
+    listener: PartialResultListener,
+    request: F,
+) -> impl Stream<Item = Result<R, anyhow::Error>> + 'a
+where
+    F: Future<Output = Result<R, anyhow::Error>> + Send + 'a,
+    R: DeserializeOwned + Send + 'a,
+{
+    struct State<'a, R> {
+        request: Option<futures::future::BoxFuture<'a, Result<R, anyhow::Error>>>,
+        listener: PartialResultListener,
+        pending: VecDeque<Result<R, anyhow::Error>>,
+    }
+
+    impl<R: DeserializeOwned> State<'_, R> {
+        fn push_partial(&mut self, partial: serde_json::Value) {
+            let partial = serde_json::from_value(partial).map_err(Into::into);
+            self.pending.push_back(partial);
+        }
+    }
+
+    let state = State {
+        request: Some(Box::pin(request)),
+        listener,
+        pending: VecDeque::new(),
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(result) = state.pending.pop_front() {
+                return Some((result, state));
+            }
+            let request = state.request.as_mut()?;
+            let partial = Box::pin(state.listener.next());
+            let outcome = match future::select(request, partial).await {
+                Either::Left((result, _)) => Either::Left(result),
+                Either::Right((partial, _)) => Either::Right(partial),
+            };
+            match outcome {
+                Either::Left(result) => {
+                    state.request = None;
+                    // Partial results reported before the response may still be queued
+                    for partial in state.listener.drain().await {
+                        state.push_partial(partial);
+                    }
+                    state.pending.push_back(result);
+                }
+                Either::Right(Some(partial)) => state.push_partial(partial),
+                // Dispatching ended, only the final result remains
+                Either::Right(None) => {
+                    let result = state.request.take()?.await;
+                    state.pending.push_back(result);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json::json;
+
+    use super::*;
+    use crate::progress::{ProgressDispatcher, RawProgressParams};
+
+    fn progress(
+        token: &ProgressToken,
+        value: serde_json::Value,
+    ) -> serde_json::Result<RawProgressParams> {
+        serde_json::from_value(json!({"token": token, "value": value}))
+    }
+
+    /// Partial results the server sent before its response are yielded, even if the
+    /// dispatcher had not forwarded them when the response arrived.
+    #[tokio::test]
+    async fn partials_reported_before_the_response_are_yielded() {
+        let token = fresh_token();
+        let other = fresh_token();
+        let notifications = futures::stream::iter(vec![
+            progress(&token, json!([1, 2])),
+            progress(&other, json!([9])),
+            progress(&token, json!([3])),
+        ])
+        .chain(futures::stream::pending());
+        let dispatcher = ProgressDispatcher::from_notifications(notifications);
+        let listener = dispatcher.listen(token);
+        let request = async { Ok(vec![4]) };
+
+        let results: Vec<Vec<u32>> = partial_results(listener, request)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(results, vec![vec![1, 2], vec![3], vec![4]]);
+    }
+
+    #[tokio::test]
+    async fn servers_without_partial_results_yield_the_response() {
+        let notifications = futures::stream::pending::<serde_json::Result<RawProgressParams>>();
+        let dispatcher = ProgressDispatcher::from_notifications(notifications);
+        let listener = dispatcher.listen(fresh_token());
+        let request = async { Ok(vec![1]) };
+
+        let results: Vec<Vec<u32>> = partial_results(listener, request)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(results, vec![vec![1]]);
+    }
+}
@@ -1,22 +1,246 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use futures::{FutureExt, Stream, StreamExt};
 use jsonrpsee::core::client::Client;
 use jsonrpsee::core::client::Subscription;
 use jsonrpsee::core::client::SubscriptionClientT;
 use lsp_types::NumberOrString;
 use lsp_types::ProgressParams;
+use lsp_types::ProgressToken;
+use serde::Deserialize;
+use tokio::sync::{broadcast, mpsc, oneshot, OnceCell};
 
-pub async fn wait_for_indexing_to_complete(client: &Client) -> Result<(), anyhow::Error> {
-    let mut waiting_for =
-        HashSet::from([NumberOrString::String("rustAnalyzer/Indexing".to_owned())]);
+/// Work done progress notifications buffered per listener before it starts lagging.
+const WORK_DONE_CAPACITY: usize = 256;
 
-    // Subscribe to notifications
-    let mut subscription: Subscription<ProgressParams> = client
-        .subscribe_to_method("$/progress")
-        .await
-        .expect("Failed to subscribe to progress notifications");
+/// A `$/progress` notification carrying an arbitrary value, such as a partial result.
+#[derive(Deserialize)]
+pub(crate) struct RawProgressParams {
+    token: ProgressToken,
+    value: serde_json::Value,
+}
+
+type PartialResultSenders =
+    Arc<Mutex<HashMap<ProgressToken, mpsc::UnboundedSender<serde_json::Value>>>>;
+
+/// Asks the dispatch task to forward everything it has received, acknowledging once done.
+type Barriers = mpsc::UnboundedSender<oneshot::Sender<()>>;
+
+/// Dispatches the `$/progress` notifications of one client by token.
+///
+/// jsonrpsee allows a single subscription per notification method, so the client
+/// subscribes once and forwards partial results to the request that registered
+/// their token, and everything else to the work done progress listeners.
+pub(crate) struct ProgressDispatcher {
+    /// Set once the dispatch task is running.
+    barriers: OnceCell<Barriers>,
+    partial_results: PartialResultSenders,
+    work_done: broadcast::Sender<ProgressParams>,
+}
+
+impl Default for ProgressDispatcher {
+    fn default() -> Self {
+        Self {
+            barriers: OnceCell::new(),
+            partial_results: Arc::default(),
+            work_done: broadcast::channel(WORK_DONE_CAPACITY).0,
+        }
+    }
+}
+
+impl ProgressDispatcher {
+    /// A dispatcher forwarding `notifications` instead of those of a client.
+    #[cfg(test)]
+    pub(crate) fn from_notifications<S, E>(notifications: S) -> Self
+    where
+        S: Stream<Item = Result<RawProgressParams, E>> + Unpin + Send + 'static,
+        E: Send + 'static,
+    {
+        let dispatcher = Self::default();
+        let barriers = dispatcher.start(notifications);
+        dispatcher.barriers.set(barriers).unwrap();
+        dispatcher
+    }
+
+    /// Subscribe to `$/progress` on the first call and dispatch until the connection closes.
+    async fn subscribe(&self, client: &Client) -> Result<(), anyhow::Error> {
+        self.barriers
+            .get_or_try_init(|| async {
+                let subscription: Subscription<RawProgressParams> =
+                    client.subscribe_to_method("$/progress").await?;
+                Ok::<_, anyhow::Error>(self.start(subscription))
+            })
+            .await?;
+        Ok(())
+    }
 
-    while let Some(notification) = subscription.next().await.transpose()? {
+    /// Dispatch `notifications` in a new task.
+    fn start<S, E>(&self, notifications: S) -> Barriers
+    where
+        S: Stream<Item = Result<RawProgressParams, E>> + Unpin + Send + 'static,
+        E: Send + 'static,
+    {
+        let (barriers, barrier_requests) = mpsc::unbounded_channel();
+        tokio::spawn(dispatch(
+            notifications,
+            barrier_requests,
+            self.partial_results.clone(),
+            self.work_done.clone(),
+        ));
+        barriers
+    }
+
+    /// The work done progress reported from now on, for any token without a
+    /// partial result listener.
+    pub(crate) async fn work_done(
+        &self,
+        client: &Client,
+    ) -> Result<impl Stream<Item = Result<ProgressParams, anyhow::Error>> + Unpin, anyhow::Error>
+    {
+        self.subscribe(client).await?;
+        Ok(self.work_done_notifications())
+    }
+
+    fn work_done_notifications(
+        &self,
+    ) -> impl Stream<Item = Result<ProgressParams, anyhow::Error>> + Unpin {
+        let receiver = self.work_done.subscribe();
+        let notifications = futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(notification) => return Some((Ok(notification), receiver)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Missed {skipped} progress notifications");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Box::pin(notifications)
+    }
+
+    /// The values reported for `token` until the returned listener is dropped.
+    pub(crate) async fn partial_results(
+        &self,
+        client: &Client,
+        token: ProgressToken,
+    ) -> Result<PartialResultListener, anyhow::Error> {
+        self.subscribe(client).await?;
+        Ok(self.listen(token))
+    }
+
+    /// Register a listener for `token` with the running dispatch task.
+    pub(crate) fn listen(&self, token: ProgressToken) -> PartialResultListener {
+        let barriers = self.barriers.get().expect("Dispatching has not started");
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.partial_results
+            .lock()
+            .unwrap()
+            .insert(token.clone(), sender);
+        PartialResultListener {
+            receiver,
+            token,
+            senders: self.partial_results.clone(),
+            barriers: barriers.clone(),
+        }
+    }
+}
+
+/// Forward each notification to the listener of its token, or to the work done listeners.
+async fn dispatch<S, E>(
+    mut notifications: S,
+    mut barrier_requests: mpsc::UnboundedReceiver<oneshot::Sender<()>>,
+    partial_results: PartialResultSenders,
+    work_done: broadcast::Sender<ProgressParams>,
+) where
+    S: Stream<Item = Result<RawProgressParams, E>> + Unpin,
+{
+    let forward = |notification: Result<RawProgressParams, E>| {
+        let Ok(RawProgressParams { token, value }) = notification else {
+            return;
+        };
+        if let Some(sender) = partial_results.lock().unwrap().get(&token) {
+            let _ = sender.send(value);
+            return;
+        }
+        if let Ok(value) = serde_json::from_value(value) {
+            // Nobody listening is not an error
+            let _ = work_done.send(ProgressParams { token, value });
+        }
+    };
+
+    loop {
+        tokio::select! {
+            notification = notifications.next() => match notification {
+                Some(notification) => forward(notification),
+                None => break,
+            },
+            Some(ack) = barrier_requests.recv() => {
+                // Forward what was received before the barrier was raised
+                while let Some(Some(notification)) = notifications.next().now_or_never() {
+                    forward(notification);
+                }
+                let _ = ack.send(());
+            }
+        }
+    }
+}
+
+/// The values reported for one partial result token. Unregisters the token when dropped.
+pub(crate) struct PartialResultListener {
+    receiver: mpsc::UnboundedReceiver<serde_json::Value>,
+    token: ProgressToken,
+    senders: PartialResultSenders,
+    barriers: Barriers,
+}
+
+impl PartialResultListener {
+    pub(crate) async fn next(&mut self) -> Option<serde_json::Value> {
+        self.receiver.recv().await
+    }
+
+    /// The values received by the client so far that have not been returned yet,
+    /// including those the dispatch task has not forwarded.
+    pub(crate) async fn drain(&mut self) -> Vec<serde_json::Value> {
+        let (ack, acknowledged) = oneshot::channel();
+        // If dispatching ended, everything it received was forwarded already
+        if self.barriers.send(ack).is_ok() {
+            let _ = acknowledged.await;
+        }
+        let mut values = Vec::new();
+        while let Ok(value) = self.receiver.try_recv() {
+            values.push(value);
+        }
+        values
+    }
+}
+
+impl Drop for PartialResultListener {
+    fn drop(&mut self) {
+        self.senders.lock().unwrap().remove(&self.token);
+    }
+}
+
+/// Progress tokens rust-analyzer reports indexing under. Older releases use
+/// `rustAnalyzer/Indexing`, newer ones `rustAnalyzer/cachePriming`.
+const INDEXING_TOKENS: [&str; 2] = ["rustAnalyzer/Indexing", "rustAnalyzer/cachePriming"];
+
+/// Wait until `notifications` reported the end of indexing, passing every progress
+/// report to `on_report`.
+pub(crate) async fn indexing<S, F>(
+    mut notifications: S,
+    mut on_report: F,
+) -> Result<(), anyhow::Error>
+where
+    S: Stream<Item = Result<ProgressParams, anyhow::Error>> + Unpin,
+    F: FnMut(&NumberOrString, Option<u32>, Option<&str>),
+{
+    let mut waiting_for = HashSet::new();
+    let mut indexed = false;
+
+    while let Some(notification) = notifications.next().await.transpose()? {
         let ProgressParams { token, value } = notification;
         let lsp_types::ProgressParamsValue::WorkDone(progress) = value;
 
@@ -24,16 +248,211 @@ pub async fn wait_for_indexing_to_complete(client: &Client) -> Result<(), anyhow
             lsp_types::WorkDoneProgress::Begin(_) => {
                 waiting_for.insert(token.clone());
             }
-            lsp_types::WorkDoneProgress::Report(_) => {}
+            lsp_types::WorkDoneProgress::Report(report) => {
+                on_report(&token, report.percentage, report.message.as_deref());
+            }
             lsp_types::WorkDoneProgress::End(_) => {
+                indexed |= matches!(
+                    &token,
+                    NumberOrString::String(token) if INDEXING_TOKENS.contains(&token.as_str())
+                );
                 waiting_for.remove(&token);
             }
         }
 
-        if waiting_for.is_empty() {
+        if indexed && waiting_for.is_empty() {
             break;
         }
     }
 
     Ok(())
 }
+
+/// Wait until `notifications` reported no active progress for `quiet_for`, or fail
+/// after `timeout`.
+pub(crate) async fn idle<S>(
+    mut notifications: S,
+    quiet_for: Duration,
+    timeout: Duration,
+) -> Result<(), anyhow::Error>
+where
+    S: Stream<Item = Result<ProgressParams, anyhow::Error>> + Unpin,
+{
+    let wait = async {
+        let mut active = HashSet::new();
+
+        loop {
+            let notification = if active.is_empty() {
+                match tokio::time::timeout(quiet_for, notifications.next()).await {
+                    Ok(notification) => notification,
+                    // The server has been quiet for long enough
+                    Err(_) => break,
+                }
+            } else {
+                notifications.next().await
+            };
+            let Some(notification) = notification.transpose()? else {
+                break;
+            };
+
+            let ProgressParams { token, value } = notification;
+            let lsp_types::ProgressParamsValue::WorkDone(progress) = value;
+
+            match progress {
+                lsp_types::WorkDoneProgress::Begin(_) => {
+                    active.insert(token);
+                }
+                lsp_types::WorkDoneProgress::Report(_) => {}
+                lsp_types::WorkDoneProgress::End(_) => {
+                    active.remove(&token);
+                }
+            }
+        }
+
+        Ok::<_, anyhow::Error>(())
+    };
+
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!(
+            "The server did not become idle within {:?}",
+            timeout
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use lsp_types::{
+        ProgressParamsValue, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressEnd,
+        WorkDoneProgressReport,
+    };
+
+    use super::*;
+
+    fn begin(token: &str) -> Result<ProgressParams, anyhow::Error> {
+        Ok(ProgressParams {
+            token: NumberOrString::String(token.to_owned()),
+            value: ProgressParamsValue::WorkDone(WorkDoneProgress::Begin(
+                WorkDoneProgressBegin::default(),
+            )),
+        })
+    }
+
+    fn end(token: &str) -> Result<ProgressParams, anyhow::Error> {
+        Ok(ProgressParams {
+            token: NumberOrString::String(token.to_owned()),
+            value: ProgressParamsValue::WorkDone(WorkDoneProgress::End(
+                WorkDoneProgressEnd::default(),
+            )),
+        })
+    }
+
+    fn report(token: &str, percentage: u32) -> Result<ProgressParams, anyhow::Error> {
+        Ok(ProgressParams {
+            token: NumberOrString::String(token.to_owned()),
+            value: ProgressParamsValue::WorkDone(WorkDoneProgress::Report(
+                WorkDoneProgressReport {
+                    percentage: Some(percentage),
+                    ..WorkDoneProgressReport::default()
+                },
+            )),
+        })
+    }
+
+    #[tokio::test]
+    async fn indexing_reports_progress_until_it_ends() {
+        let indexing_token = "rustAnalyzer/Indexing";
+        let notifications = stream::iter([
+            begin(indexing_token),
+            report(indexing_token, 50),
+            report("other", 10),
+            end(indexing_token),
+        ])
+        .chain(stream::pending());
+
+        let mut reports = Vec::new();
+        indexing(notifications, |token, percentage, _| {
+            reports.push((token.clone(), percentage));
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            reports,
+            vec![
+                (NumberOrString::String(indexing_token.to_owned()), Some(50)),
+                (NumberOrString::String("other".to_owned()), Some(10)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn indexing_ends_with_cache_priming() {
+        let notifications = stream::iter([
+            begin("rustAnalyzer/Fetching"),
+            end("rustAnalyzer/Fetching"),
+            begin("rustAnalyzer/cachePriming"),
+            begin("rust-analyzer/flycheck/0"),
+            end("rustAnalyzer/cachePriming"),
+            end("rust-analyzer/flycheck/0"),
+        ])
+        .chain(stream::pending());
+
+        let wait = indexing(notifications, |_, _, _| {});
+        let result = tokio::time::timeout(Duration::from_secs(5), wait).await;
+        assert!(matches!(result, Ok(Ok(()))));
+    }
+
+    #[tokio::test]
+    async fn idle_once_every_progress_ended() {
+        let notifications =
+            stream::iter([begin("a"), begin("b"), end("a"), end("b")]).chain(stream::pending());
+        let result = idle(
+            notifications,
+            Duration::from_millis(10),
+            Duration::from_secs(5),
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn progress_that_never_ends_times_out() {
+        let notifications = stream::iter([begin("a")]).chain(stream::pending());
+        let result = idle(
+            notifications,
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn notifications_are_dispatched_by_token() {
+        let partial_token = NumberOrString::String("partial".to_owned());
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let dispatcher = ProgressDispatcher::from_notifications(receiver);
+        let mut listener = dispatcher.listen(partial_token.clone());
+        let mut work_done = dispatcher.work_done_notifications();
+
+        let raw =
+            |params: ProgressParams| serde_json::from_value(serde_json::to_value(params).unwrap());
+        sender.unbounded_send(raw(begin("a").unwrap())).unwrap();
+        let partial = serde_json::json!({"token": partial_token, "value": [1]});
+        sender
+            .unbounded_send(serde_json::from_value(partial))
+            .unwrap();
+
+        let ProgressParams { token, .. } = work_done.next().await.unwrap().unwrap();
+        assert_eq!(token, NumberOrString::String("a".to_owned()));
+        assert_eq!(listener.next().await, Some(serde_json::json!([1])));
+
+        // Once the listener is gone, its token counts as work done progress
+        drop(listener);
+        sender.unbounded_send(raw(end("partial").unwrap())).unwrap();
+        let ProgressParams { token, .. } = work_done.next().await.unwrap().unwrap();
+        assert_eq!(token, partial_token);
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::ChildStderr;
+
+/// The number of raw stderr lines retained.
+const MAX_RETAINED_LINES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Infer the level of a server log line such as `[ERROR rust_analyzer::main_loop] ...`.
+/// Lines without a recognizable level are classified as `Info`.
+pub fn classify_line(line: &str) -> LogLevel {
+    for word in line.split_whitespace().take(3) {
+        let word = word.trim_matches(|c: char| !c.is_ascii_alphabetic());
+        match word {
+            "ERROR" => return LogLevel::Error,
+            "WARN" | "WARNING" => return LogLevel::Warn,
+            "INFO" => return LogLevel::Info,
+            "DEBUG" => return LogLevel::Debug,
+            "TRACE" => return LogLevel::Trace,
+            _ => {}
+        }
+    }
+    LogLevel::Info
+}
+
+/// Forward the server's stderr to `tracing`, retaining the most recent raw lines.
+pub(crate) fn forward_stderr(
+    stderr: ChildStderr,
+    retained: Arc<Mutex<VecDeque<String>>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            match classify_line(&line) {
+                LogLevel::Error => tracing::error!(target: "lsp_server", "{}", line),
+                LogLevel::Warn => tracing::warn!(target: "lsp_server", "{}", line),
+                LogLevel::Info => tracing::info!(target: "lsp_server", "{}", line),
+                LogLevel::Debug => tracing::debug!(target: "lsp_server", "{}", line),
+                LogLevel::Trace => tracing::trace!(target: "lsp_server", "{}", line),
+            }
+            let mut retained = retained.lock().unwrap();
+            if retained.len() == MAX_RETAINED_LINES {
+                retained.pop_front();
+            }
+            retained.push_back(line);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_line_recognizes_server_log_formats() {
+        let cases = [
+            (
+                "[ERROR rust_analyzer::main_loop] flycheck failed",
+                LogLevel::Error,
+            ),
+            ("[WARN project_model::workspace] no sysroot", LogLevel::Warn),
+            ("[INFO rust_analyzer] server version 1.0", LogLevel::Info),
+            (
+                "2024-05-01T10:00:00.123456Z ERROR rust_analyzer::reload: failed to load",
+                LogLevel::Error,
+            ),
+            (
+                "2024-05-01T10:00:00.123456Z  WARN rust_analyzer::config: unknown key",
+                LogLevel::Warn,
+            ),
+            ("   0.012345s DEBUG hir_def: lowering", LogLevel::Debug),
+            ("TRACE: entering request", LogLevel::Trace),
+            ("WARNING: deprecated option", LogLevel::Warn),
+            ("thread 'main' panicked at src/main.rs:1:1", LogLevel::Info),
+            ("", LogLevel::Info),
+        ];
+        for (line, level) in cases {
+            assert_eq!(classify_line(line), level, "{line:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn forwarded_lines_are_retained() {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "printf 'first\\nsecond\\n' >&2"])
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let retained = Arc::new(Mutex::new(VecDeque::new()));
+        forward_stderr(child.stderr.take().unwrap(), retained.clone())
+            .await
+            .unwrap();
+        child.wait().await.unwrap();
+
+        assert_eq!(*retained.lock().unwrap(), ["first", "second"]);
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{ChildStdin, ChildStdout};
 use tokio::sync::Mutex;
 
@@ -19,8 +19,16 @@ pub enum StdioTransportError {
     ParseInt(#[from] std::num::ParseIntError),
     #[error("FromUtf8 Error: {0}")]
     FromUtf8(#[from] std::string::FromUtf8Error),
+    #[error("Content-Length {0} exceeds the limit of {MAX_CONTENT_LENGTH} bytes")]
+    ContentTooLong(usize),
 }
 
+/// The largest message body the receiver accepts, guarding against corrupt headers.
+const MAX_CONTENT_LENGTH: usize = 128 * 1024 * 1024;
+
+/// The capacity the header line buffer is shrunk back to after an unusually long line.
+const LINE_CAPACITY: usize = 256;
+
 pub struct StdioSender {
     sender: Arc<Mutex<ChildStdin>>,
 }
@@ -50,30 +58,87 @@ impl TransportSenderT for StdioSender {
 }
 
 pub struct StdioReceiver {
-    reader: Arc<Mutex<BufReader<ChildStdout>>>,
+    state: Arc<Mutex<ReceiverState>>,
+    /// Skip malformed messages instead of failing, see [`StdioReceiver::new`].
+    resync_on_framing_error: bool,
+}
+
+/// Reader together with the header line buffer that is reused across messages.
+struct ReceiverState<R = ChildStdout> {
+    reader: BufReader<R>,
+    line: String,
+    /// Whether `line` already holds the first header line of the next message.
+    resumed: bool,
 }
 
 impl StdioReceiver {
-    pub fn new(stdout: ChildStdout) -> Self {
+    /// With `resync_on_framing_error`, a message with a malformed header or a body that
+    /// is not UTF-8 is skipped by scanning for the next `Content-Length` header,
+    /// instead of failing and closing the connection.
+    pub fn new(stdout: ChildStdout, resync_on_framing_error: bool) -> Self {
         Self {
-            reader: Arc::new(Mutex::new(BufReader::new(stdout))),
+            state: Arc::new(Mutex::new(ReceiverState::new(stdout))),
+            resync_on_framing_error,
         }
     }
 }
 
-#[async_trait]
-impl TransportReceiverT for StdioReceiver {
-    type Error = StdioTransportError;
+impl StdioTransportError {
+    /// Whether the error is caused by a malformed message rather than the connection.
+    fn is_framing_error(&self) -> bool {
+        match self {
+            StdioTransportError::ParseInt(_)
+            | StdioTransportError::FromUtf8(_)
+            | StdioTransportError::ContentTooLong(_) => true,
+            StdioTransportError::Io(error) => error.kind() == io::ErrorKind::InvalidData,
+            StdioTransportError::Serde(_) => false,
+        }
+    }
+}
 
-    async fn receive(&mut self) -> Result<ReceivedMessage, Self::Error> {
-        let mut reader = self.reader.lock().await;
+impl<R: AsyncRead + Unpin> ReceiverState<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            line: String::new(),
+            resumed: false,
+        }
+    }
+
+    /// Read the next message, skipping malformed ones if `resync_on_framing_error` is set.
+    async fn next_message(
+        &mut self,
+        resync_on_framing_error: bool,
+    ) -> Result<String, StdioTransportError> {
+        loop {
+            match self.read_message().await {
+                Ok(message) => return Ok(message),
+                Err(error) if resync_on_framing_error && error.is_framing_error() => {
+                    tracing::warn!("Skipping malformed message from the server: {}", error);
+                    self.skip_to_next_header().await?;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    async fn read_message(&mut self) -> Result<String, StdioTransportError> {
+        let ReceiverState {
+            reader,
+            line,
+            resumed,
+        } = self;
         let mut buf = String::new();
         let mut content_length = None;
 
         // Read headers
         loop {
-            let mut line = String::new();
-            reader.read_line(&mut line).await?;
+            if !std::mem::take(resumed) {
+                line.clear();
+                if reader.read_line(line).await? == 0 {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+            }
             if line == "\r\n" {
                 break;
             }
@@ -83,17 +148,142 @@ impl TransportReceiverT for StdioReceiver {
                     .trim()
                     .parse()
                     .map_err(StdioTransportError::from)?;
+                if len > MAX_CONTENT_LENGTH {
+                    return Err(StdioTransportError::ContentTooLong(len));
+                }
                 content_length = Some(len);
             }
         }
+        line.shrink_to(LINE_CAPACITY);
 
-        // Read message body
+        // Read the body straight into the allocation handed to jsonrpsee, without copying
         if let Some(len) = content_length {
             let mut body = vec![0; len];
             reader.read_exact(&mut body).await?;
             buf = String::from_utf8(body).map_err(StdioTransportError::from)?;
         }
 
-        Ok(ReceivedMessage::Text(buf))
+        Ok(buf)
+    }
+
+    /// Discard input up to the next `Content-Length` header, which is left in `line`.
+    async fn skip_to_next_header(&mut self) -> Result<(), StdioTransportError> {
+        const HEADER: &[u8] = b"Content-Length:";
+        let mut bytes = Vec::new();
+        loop {
+            bytes.clear();
+            if self.reader.read_until(b'\n', &mut bytes).await? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            let start = bytes
+                .windows(HEADER.len())
+                .position(|window| window == HEADER);
+            if let Some(start) = start {
+                self.line = String::from_utf8_lossy(&bytes[start..]).into_owned();
+                self.resumed = true;
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TransportReceiverT for StdioReceiver {
+    type Error = StdioTransportError;
+
+    async fn receive(&mut self) -> Result<ReceivedMessage, Self::Error> {
+        let mut state = self.state.lock().await;
+        let message = state.next_message(self.resync_on_framing_error).await?;
+        Ok(ReceivedMessage::Text(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::process::Stdio;
+
+    #[cfg(unix)]
+    use tokio::process::{Child, Command};
+
+    use super::*;
+
+    const MESSAGE: &str = r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#;
+
+    /// Spawn `cat`, which echoes everything sent to it back as a server would.
+    #[cfg(unix)]
+    fn spawn_echo() -> Child {
+        Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    async fn receive_text(receiver: &mut StdioReceiver) -> String {
+        match receiver.receive().await.unwrap() {
+            ReceivedMessage::Text(text) => text,
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn messages_round_trip() {
+        let mut child = spawn_echo();
+        let mut sender = StdioSender::new(child.stdin.take().unwrap());
+        let mut receiver = StdioReceiver::new(child.stdout.take().unwrap(), false);
+
+        let messages = [
+            r#"{"jsonrpc":"2.0","method":"a"}"#.to_owned(),
+            format!(r#"{{"jsonrpc":"2.0","method":"{}"}}"#, "x".repeat(10_000)),
+            r#"{"jsonrpc":"2.0","method":"ünïcödé"}"#.to_owned(),
+        ];
+        for message in &messages {
+            sender.send(message.clone()).await.unwrap();
+        }
+        for message in &messages {
+            assert_eq!(&receive_text(&mut receiver).await, message);
+        }
+        child.kill().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn oversized_content_length_is_rejected() {
+        let mut child = spawn_echo();
+        let mut stdin = child.stdin.take().unwrap();
+        let mut receiver = StdioReceiver::new(child.stdout.take().unwrap(), false);
+
+        let header = format!("Content-Length: {}\r\n\r\n", MAX_CONTENT_LENGTH + 1);
+        stdin.write_all(header.as_bytes()).await.unwrap();
+        stdin.flush().await.unwrap();
+        let error = receiver.receive().await.unwrap_err();
+        assert!(matches!(error, StdioTransportError::ContentTooLong(_)));
+        child.kill().await.unwrap();
+    }
+
+    /// A frame with an unparsable length, followed by noise and a valid frame.
+    fn garbage_then_valid() -> Vec<u8> {
+        let mut input = b"Content-Length: many\r\n\r\nnoise\r\n".to_vec();
+        input.extend(format!("Content-Length: {}\r\n\r\n{}", MESSAGE.len(), MESSAGE).bytes());
+        input
+    }
+
+    #[tokio::test]
+    async fn resync_skips_garbage_frame() {
+        let input = garbage_then_valid();
+        let mut state = ReceiverState::new(input.as_slice());
+        assert_eq!(state.next_message(true).await.unwrap(), MESSAGE);
+        assert!(state.next_message(true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn garbage_frame_fails_without_resync() {
+        let input = garbage_then_valid();
+        let mut state = ReceiverState::new(input.as_slice());
+        let error = state.next_message(false).await.unwrap_err();
+        assert!(error.is_framing_error());
     }
 }